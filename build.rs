@@ -0,0 +1,70 @@
+//! Links `libmagic`, dynamically by default
+//!
+//! The `static` feature links `libmagic.a` instead, together with the compression
+//! libraries it depends on. `MAGIC_LIB_DIR` adds a directory to search for either one,
+//! otherwise a static `libmagic` is located with `pkg-config` if it is installed.
+//!
+//! `MAGIC_LINK_NAME` overrides the name of the library to link, e.g. `magic-static` for
+//! `libmagic-static.a`, to use a vendored or patched build. Such a build is not looked
+//! up with `pkg-config`, set `MAGIC_LIB_DIR` as well unless the linker finds it anyway.
+//!
+//! On Windows with MSVC the import library is expected to be called `libmagic.lib`, as
+//! e.g. `vcpkg install libmagic` provides it.
+use std::{env, process::Command};
+
+/// The libraries a static `libmagic` usually needs, if `pkg-config` can't tell
+const STATIC_DEPS: &[&str] = &["lzma", "bz2", "z"];
+
+/// Runs `pkg-config` with `args` for `libmagic`, returning its output on success
+fn pkg_config(args: &[&str]) -> Option<String> {
+    let output = Command::new("pkg-config").args(args).arg("libmagic").output().ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=MAGIC_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=MAGIC_LINK_NAME");
+
+    let lib_dir = env::var("MAGIC_LIB_DIR").ok();
+    if let Some(ref dir) = lib_dir {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").map(|e| e == "msvc").unwrap_or(false);
+    let link_name = env::var("MAGIC_LINK_NAME").ok();
+    let name = match link_name {
+        Some(ref name) => name.as_str(),
+        None if msvc => "libmagic",
+        None => "magic",
+    };
+
+    if env::var_os("CARGO_FEATURE_STATIC").is_none() {
+        println!("cargo:rustc-link-lib={}", name);
+        return;
+    }
+
+    // Unlike the linker, rustc does not search the system directories for static libraries
+    if lib_dir.is_none() && link_name.is_none() {
+        if let Some(dir) = pkg_config(&["--variable=libdir"]) {
+            println!("cargo:rustc-link-search=native={}", dir.trim());
+        }
+    }
+    println!("cargo:rustc-link-lib=static={}", name);
+
+    let deps = match pkg_config(&["--static", "--libs-only-l"]) {
+        Some(libs) => libs
+            .split_whitespace()
+            .filter_map(|l| l.strip_prefix("-l"))
+            .filter(|&l| l != "magic" && l != name)
+            .map(|l| l.to_string())
+            .collect(),
+        None => STATIC_DEPS.iter().map(|l| l.to_string()).collect::<Vec<_>>(),
+    };
+    for dep in deps {
+        println!("cargo:rustc-link-lib={}", dep);
+    }
+}
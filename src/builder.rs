@@ -0,0 +1,62 @@
+/// Fluent configuration of a `Magic`
+use std::path::{Path, PathBuf};
+
+use {FileMagicError, Flags, Magic, Param};
+
+/// Builds a ready to use `Magic` in a single expression
+///
+/// ```no_run
+/// use filemagic::{Flags, MagicBuilder, Param};
+///
+/// let magic = MagicBuilder::new()
+///     .flag(Flags::MIME_TYPE)
+///     .database("data/db-images-png")
+///     .param(Param::BytesMax, 1024 * 1024)
+///     .open()
+///     .expect("error");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MagicBuilder {
+    flags: Flags,
+    databases: Vec<PathBuf>,
+    params: Vec<(Param, usize)>,
+}
+
+impl MagicBuilder {
+    /// Creates a builder without any flags, using the default database
+    pub fn new() -> MagicBuilder {
+        Default::default()
+    }
+
+    /// Adds `flag` to the flags to open with
+    pub fn flag(mut self, flag: Flags) -> MagicBuilder {
+        self.flags.insert(flag);
+        self
+    }
+
+    /// Adds `database` to the databases to load, instead of the default database
+    pub fn database<P: AsRef<Path>>(mut self, database: P) -> MagicBuilder {
+        self.databases.push(database.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the limit `param` to `value` once opened
+    pub fn param(mut self, param: Param, value: usize) -> MagicBuilder {
+        self.params.push((param, value));
+        self
+    }
+
+    /// Opens a `Magic` with the flags, sets the params and loads the databases
+    ///
+    /// Fails with the first error of any of these steps.
+    pub fn open(self) -> Result<Magic, FileMagicError> {
+        let magic = Magic::open(self.flags)?;
+        for (param, value) in self.params {
+            if !magic.set_param(param, value) {
+                return Err(FileMagicError::new(format!("unsupported parameter {:?}", param), 0));
+            }
+        }
+        magic.load(&self.databases)?;
+        Ok(magic)
+    }
+}
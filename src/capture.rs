@@ -0,0 +1,101 @@
+//! Capturing of what `libmagic` prints on its own to `stdout` or `stderr`
+use libc::{self, c_int};
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem::ManuallyDrop,
+    os::unix::io::{AsRawFd, FromRawFd},
+    ptr,
+    sync::Mutex,
+};
+
+/// Serializes redirections, since they are process wide
+static LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// The file descriptors this thread is capturing, outermost first, while it holds `LOCK`
+    static ACTIVE: RefCell<Vec<c_int>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes the innermost capture from `ACTIVE`, even if the captured call panics
+struct Active;
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.borrow_mut().pop());
+    }
+}
+
+/// Puts the original file descriptor back in place, even if the captured call panics
+struct Redirect {
+    fd: c_int,
+    saved: c_int,
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        unsafe {
+            libc::fflush(ptr::null_mut());
+            libc::dup2(self.saved, self.fd);
+            libc::close(self.saved);
+        }
+    }
+}
+
+/// Runs `f` while the file descriptor `fd` is redirected into a temporary file
+///
+/// Returns the result of `f` along with everything written to `fd` meanwhile. As the
+/// redirection is process wide, output of other threads to `fd` is captured as well.
+///
+/// Captures may be nested within `f`. What an inner capture of the same `fd` collects is
+/// passed on to the outer one as well.
+pub fn capture<T, F: FnOnce() -> T>(fd: c_int, f: F) -> io::Result<(T, Vec<u8>)> {
+    let (nested, outer) = ACTIVE.with(|active| {
+        let active = active.borrow();
+        (!active.is_empty(), active.contains(&fd))
+    });
+    // The lock is already held by this thread if it is nested
+    let _lock = if nested { None } else { Some(LOCK.lock().unwrap_or_else(|e| e.into_inner())) };
+    ACTIVE.with(|active| active.borrow_mut().push(fd));
+    let _active = Active;
+
+    let mut tmp = unsafe {
+        let stream = libc::tmpfile();
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let tmp_fd = libc::dup(libc::fileno(stream));
+        libc::fclose(stream);
+        if tmp_fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        File::from_raw_fd(tmp_fd)
+    };
+
+    let ret = unsafe {
+        // Don't capture what others buffered before
+        libc::fflush(ptr::null_mut());
+        let saved = libc::dup(fd);
+        if saved == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let redirect = Redirect { fd, saved };
+        if libc::dup2(tmp.as_raw_fd(), fd) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let ret = f();
+        drop(redirect);
+        ret
+    };
+
+    let mut output = Vec::new();
+    tmp.seek(SeekFrom::Start(0))?;
+    tmp.read_to_end(&mut output)?;
+    if outer {
+        // `fd` is the redirection of the outer capture again, which must not be closed here
+        let mut outer = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+        outer.write_all(&output)?;
+    }
+    Ok((ret, output))
+}
@@ -0,0 +1,52 @@
+/// Structured listings of magic entries
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A magic entry as listed by `libmagic`, see `Magic::list_entries`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MagicEntry {
+    /// The description reported on a match, e.g. `PNG image data`
+    pub description: String,
+    /// The MIME type reported on a match, if the entry has one
+    pub mime_type: Option<String>,
+    /// How strongly a match is ranked, stronger entries are tried first
+    pub strength: usize,
+    /// The line of the entry in its `source_file`
+    pub line: usize,
+    /// The database file the entry comes from
+    pub source_file: PathBuf,
+}
+
+// Listings are only captured on Unix, see `Magic::list_entries`
+#[cfg(unix)]
+impl MagicEntry {
+    /// Parses the listing `libmagic` prints for the single database `source_file`
+    ///
+    /// Entries are listed as `Strength = 110@1: PNG image data [image/png]`, any other
+    /// lines such as the headers of the sets are skipped.
+    pub(crate) fn parse_listing(listing: &str, source_file: &Path) -> Vec<MagicEntry> {
+        listing
+            .lines()
+            .filter_map(|line| MagicEntry::parse(line, source_file))
+            .collect()
+    }
+
+    fn parse(line: &str, source_file: &Path) -> Option<MagicEntry> {
+        let line = line.strip_prefix("Strength = ")?;
+        let (strength, rest) = line.split_once('@')?;
+        let (number, rest) = rest.split_once(": ")?;
+        // The MIME type is always bracketed, but may be empty
+        let (description, mime_type) = match rest.rfind(" [") {
+            Some(start) if rest.ends_with(']') => (&rest[..start], &rest[start + 2..rest.len() - 1]),
+            _ => (rest, ""),
+        };
+        Some(MagicEntry {
+            description: description.to_string(),
+            mime_type: if mime_type.is_empty() { None } else { Some(mime_type.to_string()) },
+            strength: strength.trim().parse().ok()?,
+            line: number.parse().ok()?,
+            source_file: source_file.to_path_buf(),
+        })
+    }
+}
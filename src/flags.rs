@@ -1,9 +1,12 @@
 /// Bitmask flags which control `libmagic` behaviour
 use libc::c_int;
+use std::{fmt, str::FromStr};
+
+use FileMagicError;
 
 bitflags! {
-    #[doc = "Bitmask flags that specify how `Cookie` functions should behave\n\nNOTE: The descriptions are taken from `man libmagic 3`."]
-    #[derive(Default)]
+    #[doc = "Bitmask flags that specify how `Cookie` functions should behave\n\nNOTE: The descriptions are taken from `man libmagic 3`. Flags newer than the linked `libmagic` are silently ignored by it."]
+    #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
     pub struct Flags: c_int {
         #[doc = "No special handling"]
         const NONE              = 0x000000;
@@ -42,8 +45,8 @@ bitflags! {
         const MIME_ENCODING     = 0x000400;
 
         #[doc = "A shorthand for `MIME_TYPE | MIME_ENCODING`"]
-        const MIME              = Self::MIME_TYPE.bits
-                                 | Self::MIME_ENCODING.bits;
+        const MIME              = Self::MIME_TYPE.bits()
+                                 | Self::MIME_ENCODING.bits();
 
         #[doc = "Return the Apple creator and type"]
         const APPLE             = 0x000800;
@@ -54,6 +57,9 @@ bitflags! {
         #[doc = "Check inside compressed files but do not report compression"]
         const COMPRESS_TRANSP   = 0x2000000;
 
+        #[doc = "Don't allow decompression that needs to fork"]
+        const NO_COMPRESS_FORK  = 0x4000000;
+
         #[doc = "Don't look inside compressed files"]
         const NO_CHECK_COMPRESS = 0x001000;
 
@@ -75,24 +81,32 @@ bitflags! {
         #[doc = "Don't get extra information on MS Composite Document Files"]
         const NO_CHECK_CDF      = 0x040000;
 
+        #[doc = "Don't check for CSV files"]
+        const NO_CHECK_CSV      = 0x080000;
+
         #[doc = "Don't look for known tokens inside ascii files"]
         const NO_CHECK_TOKENS   = 0x100000;
 
         #[doc = "Don't check text encodings"]
         const NO_CHECK_ENCODING = 0x200000;
 
+        #[doc = "Don't check for JSON files"]
+        const NO_CHECK_JSON     = 0x400000;
+
         #[doc = "No built-in tests; only consult the magic file"]
-        const NO_CHECK_BUILTIN  = Self::NO_CHECK_COMPRESS.bits
-                                 | Self::NO_CHECK_TAR.bits
-                                 | Self::NO_CHECK_APPTYPE.bits
-                                 | Self::NO_CHECK_ELF.bits
-                                 | Self::NO_CHECK_TEXT.bits
-                                 | Self::NO_CHECK_CDF.bits
-                                 | Self::NO_CHECK_TOKENS.bits
-                                 | Self::NO_CHECK_ENCODING.bits;
+        const NO_CHECK_BUILTIN  = Self::NO_CHECK_COMPRESS.bits()
+                                 | Self::NO_CHECK_TAR.bits()
+                                 | Self::NO_CHECK_APPTYPE.bits()
+                                 | Self::NO_CHECK_ELF.bits()
+                                 | Self::NO_CHECK_TEXT.bits()
+                                 | Self::NO_CHECK_CSV.bits()
+                                 | Self::NO_CHECK_CDF.bits()
+                                 | Self::NO_CHECK_TOKENS.bits()
+                                 | Self::NO_CHECK_ENCODING.bits()
+                                 | Self::NO_CHECK_JSON.bits();
 
         #[doc = "Don't look inside ascii files"]
-        const NO_CHECK_ASCII = Self::NO_CHECK_TEXT.bits;
+        const NO_CHECK_ASCII = Self::NO_CHECK_TEXT.bits();
 
         #[doc = "Don't check ascii/fortran"]
         const NO_CHECK_FORTRAN = 0x000000;
@@ -101,3 +115,192 @@ bitflags! {
         const NO_CHECK_TROFF = 0x000000;
     }
 }
+
+impl Flags {
+    /// Splits the raw `bits` into the known `Flags` and the bits unknown to this crate
+    ///
+    /// Useful to validate bitmasks coming from e.g. a config file, the unknown bits are
+    /// `0` when all of them are known.
+    pub fn split_bits(bits: c_int) -> (Flags, c_int) {
+        let known = Flags::from_bits_truncate(bits);
+        (known, bits & !known.bits())
+    }
+
+    /// Checks for contradictory or ineffective combinations of flags
+    ///
+    /// `libmagic` accepts these silently, so this reports all of them in a single error
+    /// before they lead to confusing results.
+    pub fn validate(&self) -> Result<(), FileMagicError> {
+        let mut problems = Vec::new();
+
+        if self.contains(Flags::COMPRESS | Flags::NO_CHECK_COMPRESS) {
+            problems.push(format!("{} contradicts {}", Flags::COMPRESS, Flags::NO_CHECK_COMPRESS));
+        }
+        if self.contains(Flags::COMPRESS_TRANSP) && !self.contains(Flags::COMPRESS) {
+            problems.push(format!("{} has no effect without {}", Flags::COMPRESS_TRANSP, Flags::COMPRESS));
+        }
+        if self.contains(Flags::NO_CHECK_SOFT | Flags::NO_CHECK_BUILTIN) {
+            problems.push(format!(
+                "{} with {} leaves nothing to check",
+                Flags::NO_CHECK_SOFT,
+                Flags::NO_CHECK_BUILTIN
+            ));
+        }
+        let outputs = [Flags::MIME, Flags::APPLE, Flags::EXTENSION]
+            .iter()
+            .filter(|&&output| self.intersects(output))
+            .count();
+        if outputs > 1 {
+            problems.push(format!(
+                "only one of {}, {} and {} can be reported",
+                Flags::MIME,
+                Flags::APPLE,
+                Flags::EXTENSION
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(FileMagicError::new(problems.join("; "), libc::EINVAL))
+        }
+    }
+
+    /// Returns the `libmagic` name of a single flag, e.g. `MAGIC_MIME_TYPE`
+    ///
+    /// Composite flags such as `MIME` have a name as well, other combinations don't.
+    /// Aliases like `NO_CHECK_ASCII` are named after the flag they alias.
+    pub fn name(&self) -> Option<&'static str> {
+        NAMES.iter().find(|&&(flag, _)| flag == *self).map(|&(_, name)| name)
+    }
+
+    /// Parses a list of flag names separated by `,` or `|`, as accepted by `from_str()`
+    ///
+    /// An empty list is `Flags::NONE`. The output of `Display`, e.g.
+    /// `MAGIC_MIME | MAGIC_ERROR`, parses back to the same flags.
+    pub fn parse_list(s: &str) -> Result<Flags, FileMagicError> {
+        s.split([',', '|'])
+            .filter(|name| !name.trim().is_empty())
+            .try_fold(Flags::NONE, |flags, name| Ok(flags | name.parse::<Flags>()?))
+    }
+
+    /// Returns the `libmagic` names of the flags, preferring composite flags
+    ///
+    /// Bits unknown to this crate, e.g. from `Magic::open_raw()`, follow in hex, e.g. `0x40000000`.
+    fn names(&self) -> Vec<String> {
+        let mut remaining = *self;
+        let mut names = Vec::new();
+        for &(flag, name) in NAMES {
+            if !flag.is_empty() && remaining.contains(flag) {
+                names.push(name.to_string());
+                remaining.remove(flag);
+            }
+        }
+        if !remaining.is_empty() {
+            names.push(format!("{:#x}", remaining.bits()));
+        }
+        names
+    }
+}
+
+/// The canonical `libmagic` names of the flags
+///
+/// Composite flags come before their parts, so that they are preferred when formatting.
+const NAMES: &[(Flags, &str)] = &[
+    (Flags::NONE, "MAGIC_NONE"),
+    (Flags::MIME, "MAGIC_MIME"),
+    (Flags::NO_CHECK_BUILTIN, "MAGIC_NO_CHECK_BUILTIN"),
+    (Flags::DEBUG, "MAGIC_DEBUG"),
+    (Flags::SYMLINK, "MAGIC_SYMLINK"),
+    (Flags::COMPRESS, "MAGIC_COMPRESS"),
+    (Flags::DEVICES, "MAGIC_DEVICES"),
+    (Flags::MIME_TYPE, "MAGIC_MIME_TYPE"),
+    (Flags::CONTINUE, "MAGIC_CONTINUE"),
+    (Flags::CHECK, "MAGIC_CHECK"),
+    (Flags::PRESERVE_ATIME, "MAGIC_PRESERVE_ATIME"),
+    (Flags::RAW, "MAGIC_RAW"),
+    (Flags::ERROR, "MAGIC_ERROR"),
+    (Flags::MIME_ENCODING, "MAGIC_MIME_ENCODING"),
+    (Flags::APPLE, "MAGIC_APPLE"),
+    (Flags::EXTENSION, "MAGIC_EXTENSION"),
+    (Flags::COMPRESS_TRANSP, "MAGIC_COMPRESS_TRANSP"),
+    (Flags::NO_COMPRESS_FORK, "MAGIC_NO_COMPRESS_FORK"),
+    (Flags::NO_CHECK_COMPRESS, "MAGIC_NO_CHECK_COMPRESS"),
+    (Flags::NO_CHECK_TAR, "MAGIC_NO_CHECK_TAR"),
+    (Flags::NO_CHECK_SOFT, "MAGIC_NO_CHECK_SOFT"),
+    (Flags::NO_CHECK_APPTYPE, "MAGIC_NO_CHECK_APPTYPE"),
+    (Flags::NO_CHECK_ELF, "MAGIC_NO_CHECK_ELF"),
+    (Flags::NO_CHECK_TEXT, "MAGIC_NO_CHECK_TEXT"),
+    (Flags::NO_CHECK_CDF, "MAGIC_NO_CHECK_CDF"),
+    (Flags::NO_CHECK_CSV, "MAGIC_NO_CHECK_CSV"),
+    (Flags::NO_CHECK_TOKENS, "MAGIC_NO_CHECK_TOKENS"),
+    (Flags::NO_CHECK_ENCODING, "MAGIC_NO_CHECK_ENCODING"),
+    (Flags::NO_CHECK_JSON, "MAGIC_NO_CHECK_JSON"),
+    (Flags::NO_CHECK_ASCII, "MAGIC_NO_CHECK_ASCII"),
+    (Flags::NO_CHECK_FORTRAN, "MAGIC_NO_CHECK_FORTRAN"),
+    (Flags::NO_CHECK_TROFF, "MAGIC_NO_CHECK_TROFF"),
+];
+
+impl fmt::Display for Flags {
+    /// Formats the flags with their `libmagic` names, e.g. `MAGIC_MIME | MAGIC_ERROR`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("MAGIC_NONE");
+        }
+        f.write_str(&self.names().join(" | "))
+    }
+}
+
+impl fmt::Debug for Flags {
+    /// Formats the flags with their `libmagic` names and their value, e.g.
+    /// `Flags(MAGIC_MIME | MAGIC_ERROR, 0x610)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Flags({}, {:#x})", self, self.bits())
+    }
+}
+
+impl FromStr for Flags {
+    type Err = FileMagicError;
+
+    /// Parses a single flag from its `libmagic` name, e.g. `MAGIC_MIME_TYPE`, or its
+    /// short form, e.g. `mime_type`
+    ///
+    /// Surrounding whitespace is ignored and the comparison is case-insensitive. Raw bits
+    /// in hex, e.g. `0x40000000` as `Display` prints bits unknown to this crate, are kept
+    /// as they are.
+    fn from_str(s: &str) -> Result<Flags, FileMagicError> {
+        let name = s.trim().to_ascii_uppercase();
+        if let Some(hex) = name.strip_prefix("0X") {
+            return u32::from_str_radix(hex, 16)
+                .map(|bits| Flags::from_bits_retain(bits as c_int))
+                .map_err(|_| FileMagicError::new(format!("unknown flag `{}`", s.trim()), 0));
+        }
+        let name = if name.starts_with("MAGIC_") { name } else { format!("MAGIC_{}", name) };
+        NAMES
+            .iter()
+            .find(|&&(_, n)| n == name)
+            .map(|&(flag, _)| flag)
+            .ok_or_else(|| FileMagicError::new(format!("unknown flag `{}`", s.trim()), 0))
+    }
+}
+
+/// Serializes the flags as a list of their `libmagic` names, e.g. `["MAGIC_MIME", "MAGIC_ERROR"]`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Flags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.names())
+    }
+}
+
+/// Deserializes the flags from a list of names, as accepted by `from_str()`
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Flags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Flags, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(Flags::NONE, |flags, name| {
+            name.parse::<Flags>()
+                .map(|flag| flags | flag)
+                .map_err(serde::de::Error::custom)
+        })
+    }
+}
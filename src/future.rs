@@ -0,0 +1,104 @@
+/// Classification from asynchronous code
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use {FileMagicError, Magic};
+
+/// The outcome of a classification and who to wake up once it is there
+#[derive(Default)]
+struct State {
+    result: Option<Result<String, FileMagicError>>,
+    waker: Option<Waker>,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+/// The data to classify and where to put the outcome
+type Job = (Vec<u8>, Shared);
+
+/// Completes the classification `shared` with `result` and wakes up its future
+fn complete(shared: &Shared, result: Result<String, FileMagicError>) {
+    let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+    state.result = Some(result);
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+/// A `Magic` running on a worker thread of its own, for use from asynchronous code
+///
+/// `libmagic` only offers blocking calls, so these are made on the worker instead of
+/// stalling an executor. The `Magic` is only ever touched by the worker, which classifies
+/// one buffer after the other. It is stopped when the `AsyncMagic` is dropped.
+pub struct AsyncMagic {
+    jobs: Mutex<Option<mpsc::Sender<Job>>>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl AsyncMagic {
+    /// Moves `magic` to a new worker thread
+    pub fn new(magic: Magic) -> Result<AsyncMagic, FileMagicError> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let worker = thread::Builder::new()
+            .name("filemagic".to_string())
+            .spawn(move || {
+                for (data, shared) in queue {
+                    complete(&shared, magic.buffer(data));
+                }
+            })?;
+        Ok(AsyncMagic {
+            jobs: Mutex::new(Some(jobs)),
+            worker: Some(worker),
+        })
+    }
+
+    /// Returns a future of the textual description of the contents of `data`
+    pub fn classify(&self, data: Vec<u8>) -> Classify {
+        let shared = Shared::default();
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let sent = match *jobs {
+            Some(ref jobs) => jobs.send((data, Arc::clone(&shared))).is_ok(),
+            None => false,
+        };
+        if !sent {
+            // Only a panic in libmagic can stop the worker early
+            complete(&shared, Err(FileMagicError::new("the worker thread has stopped", 0)));
+        }
+        Classify { shared }
+    }
+}
+
+impl Drop for AsyncMagic {
+    /// Stops the worker after the pending classifications, closing its `Magic`
+    fn drop(&mut self) {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A classification in progress, see `AsyncMagic::classify`
+pub struct Classify {
+    shared: Shared,
+}
+
+impl Future for Classify {
+    type Output = Result<String, FileMagicError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/// Iterators classifying many files
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    slice,
+};
+
+use {FileMagicError, Magic};
+
+/// Iterator over the classified entries of a directory, see `Magic::walk_dir`
+pub struct WalkDir<'a> {
+    magic: &'a Magic,
+    recursive: bool,
+    /// The directories being read, innermost last
+    dirs: Vec<(PathBuf, fs::ReadDir)>,
+    /// An error to report before anything else, e.g. opening the top directory
+    error: Option<(PathBuf, io::Error)>,
+}
+
+impl<'a> WalkDir<'a> {
+    pub(crate) fn new(magic: &'a Magic, dir: &Path, recursive: bool) -> WalkDir<'a> {
+        let mut walk = WalkDir {
+            magic,
+            recursive,
+            dirs: Vec::new(),
+            error: None,
+        };
+        match fs::read_dir(dir) {
+            Ok(entries) => walk.dirs.push((dir.to_path_buf(), entries)),
+            Err(e) => walk.error = Some((dir.to_path_buf(), e)),
+        }
+        walk
+    }
+}
+
+impl<'a> Iterator for WalkDir<'a> {
+    type Item = (PathBuf, Result<String, FileMagicError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((path, e)) = self.error.take() {
+            return Some((path, Err(e.into())));
+        }
+
+        loop {
+            let entry = match self.dirs.last_mut() {
+                None => return None,
+                Some(&mut (ref dir, ref mut entries)) => match entries.next() {
+                    None => None,
+                    Some(Err(e)) => return Some((dir.clone(), Err(e.into()))),
+                    Some(Ok(entry)) => Some(entry),
+                },
+            };
+            let entry = match entry {
+                None => {
+                    self.dirs.pop();
+                    continue;
+                }
+                Some(entry) => entry,
+            };
+
+            let path = entry.path();
+            // The file type of an entry is that of a symlink itself, so symlinks to
+            // directories are never descended into
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if self.recursive && is_dir {
+                match fs::read_dir(&path) {
+                    Ok(entries) => self.dirs.push((path.clone(), entries)),
+                    Err(e) => return Some((path, Err(e.into()))),
+                }
+            }
+            let result = self.magic.file(&path);
+            return Some((path, result));
+        }
+    }
+}
+
+/// Iterator over a slice of paths and their descriptions, see `Magic::files`
+pub struct Files<'a, P: 'a> {
+    magic: &'a Magic,
+    paths: slice::Iter<'a, P>,
+}
+
+impl<'a, P: AsRef<Path>> Files<'a, P> {
+    pub(crate) fn new(magic: &'a Magic, paths: &'a [P]) -> Files<'a, P> {
+        Files {
+            magic,
+            paths: paths.iter(),
+        }
+    }
+}
+
+impl<'a, P: AsRef<Path>> Iterator for Files<'a, P> {
+    type Item = (&'a P, Result<String, FileMagicError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.paths.next().map(|path| (path, self.magic.file(path)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.paths.size_hint()
+    }
+}
@@ -17,7 +17,7 @@
 //! fn main() {
 //!    let test_file = "path/to/file";
 //!    let cookie = Magic::open(Default::default()).expect("error");
-//!     cookie.load::<String>(&[]).expect("error");
+//!     cookie.load_default().expect("error");
 //!     let magic = cookie.file(&test_file).expect("error in magic");
 //!     println!("magic= {}", magic);
 //! }
@@ -29,42 +29,309 @@ extern crate bitflags;
 pub mod macros;
 
 extern crate libc;
-use libc::{c_char, size_t};
+#[cfg(feature = "serde")]
+extern crate serde;
+use libc::{c_char, c_int, c_void, size_t};
 
 mod api;
+#[cfg(unix)]
+mod capture;
+mod tar;
 
 pub mod version;
-pub use version::version;
+pub use version::{libmagic_version, version};
 
 pub mod flags;
 pub use flags::Flags;
 
+pub mod param;
+pub use param::{Limits, Param};
+
+pub mod mime;
+pub use mime::{CommonMime, Encoding, Mime};
+
+pub mod entry;
+pub use entry::MagicEntry;
+
+pub mod sync;
+pub use sync::{MagicPool, PooledMagic, SyncMagic};
+
+pub mod builder;
+pub use builder::MagicBuilder;
+
+pub mod iter;
+pub use iter::{Files, WalkDir};
+
+pub mod reader;
+pub use reader::ClassifyingReader;
+
+#[cfg(feature = "async")]
+pub mod future;
+#[cfg(feature = "async")]
+pub use future::{AsyncMagic, Classify};
+
 #[cfg(test)]
 mod tests;
 
 use std::{
+    cell::{Cell, RefCell},
     error,
-    ffi::{CStr, CString},
+    ffi::CString,
     fmt::Display,
-    path::Path,
+    io::{self, Read},
+    path::{Path, PathBuf},
     ptr, str,
 };
 
-fn db_filenames<P: AsRef<Path>>(filenames: &[P]) -> *const c_char {
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+
+/// What empty buffers are passed to `libmagic` as
+static EMPTY: [u8; 1] = [0];
+
+/// The separator of lists of databases, as `libmagic` defines it for the platform
+#[cfg(windows)]
+pub const PATH_SEPARATOR: char = ';';
+/// The separator of lists of databases, as `libmagic` defines it for the platform
+#[cfg(not(windows))]
+pub const PATH_SEPARATOR: char = ':';
+
+/// The MIME types `libmagic` falls back to for contents it doesn't recognize
+///
+/// `application/octet-stream` is what `libmagic` describes as `data` otherwise. See
+/// `Magic::is_recognized`.
+pub const GENERIC_MIME_TYPES: &[&str] = &["application/octet-stream"];
+
+/// The textual descriptions `libmagic` falls back to for contents it doesn't recognize
+///
+/// `data` is what libmagic 5.44 reports for binary contents none of the entries match.
+/// See `Magic::set_unknown_as`.
+pub const GENERIC_DESCRIPTIONS: &[&str] = &["data"];
+
+/// Returns whether `result` is one of the generic fallbacks of `libmagic`
+///
+/// A MIME type matches regardless of its charset, e.g. `application/octet-stream; charset=binary`.
+fn is_generic(result: &str) -> bool {
+    let mime_type = result.split(';').next().unwrap_or("");
+    GENERIC_DESCRIPTIONS.contains(&result) || GENERIC_MIME_TYPES.contains(&mime_type)
+}
+
+/// The MIME types of common archives, see `Magic::is_archive`
+pub const ARCHIVE_MIME_TYPES: &[&str] = &[
+    "application/zip",
+    "application/x-tar",
+    "application/gzip",
+    "application/x-7z-compressed",
+    "application/x-rar",
+    "application/vnd.rar",
+];
+
+/// The MIME types `libmagic` reports for compression formats, with their names
+const COMPRESSION_MIME_TYPES: &[(&str, &str)] = &[
+    ("application/gzip", "gzip"),
+    ("application/x-bzip2", "bzip2"),
+    ("application/x-xz", "xz"),
+    ("application/zstd", "zstd"),
+    ("application/x-lzma", "lzma"),
+    ("application/x-lzip", "lzip"),
+    ("application/x-lz4", "lz4"),
+    ("application/x-compress", "compress"),
+];
+
+/// Parses the value of the `MAGIC_FLAGS` environment variable, `None` if it is not set
+fn env_flags(value: Option<&str>) -> Result<Flags, FileMagicError> {
+    match value {
+        Some(list) => Flags::parse_list(list).map_err(|mut e| {
+            e.desc = format!("MAGIC_FLAGS: {}", e.desc);
+            e
+        }),
+        None => Ok(Flags::NONE),
+    }
+}
+
+/// Converts `bits` to `Flags`, keeping any bits unknown to this crate
+fn raw_flags(bits: c_int) -> Flags {
+    Flags::from_bits_retain(bits)
+}
+
+/// Returns the raw bytes of `path`, which on Unix need not be valid UTF-8
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+fn nul_error(bytes: Vec<u8>) -> FileMagicError {
+    FileMagicError::new(
+        format!("path `{}` contains a NUL byte", String::from_utf8_lossy(&bytes)),
+        libc::EINVAL,
+    )
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString, FileMagicError> {
+    CString::new(path_bytes(path)).map_err(|e| nul_error(e.into_vec()))
+}
+
+/// Joins the database `filenames` into the `PATH_SEPARATOR` separated list `libmagic` expects
+///
+/// No filenames at all means the default database, which `libmagic` expects as `NULL`.
+fn db_filenames<P: AsRef<Path>>(filenames: &[P]) -> Result<Option<CString>, FileMagicError> {
     match filenames.len() {
-        0 => ptr::null(),
-        // FIXME: This is just plain wrong. I'm surprised it works at all..
-        1 => CString::new(filenames[0].as_ref().to_string_lossy().into_owned())
-            .unwrap()
-            .into_raw(),
-        _ => unimplemented!(),
+        0 => Ok(None),
+        _ => {
+            let joined = filenames
+                .iter()
+                .map(|f| path_bytes(f.as_ref()))
+                .collect::<Vec<_>>()
+                .join(&(PATH_SEPARATOR as u8));
+            CString::new(joined).map(Some).map_err(|e| nul_error(e.into_vec()))
+        }
+    }
+}
+
+fn db_filenames_ptr(filenames: &Option<CString>) -> *const c_char {
+    filenames.as_ref().map_or(ptr::null(), |f| f.as_ptr())
+}
+
+/// Splits a `Flags::CONTINUE` result into the single matches
+///
+/// `libmagic` separates them with `\n- `, which is printed as `\012- ` unless `Flags::RAW`
+/// is set.
+fn split_matches(all: &str) -> Vec<String> {
+    all.replace("\\012- ", "\n- ")
+        .split("\n- ")
+        .map(|m| m.to_string())
+        .collect()
+}
+
+/// Returns the architecture named in the textual `description` of an ELF, PE or Mach-O file
+///
+/// e.g. `x86-64` from `ELF 64-bit LSB pie executable, x86-64, version 1 (SYSV), ...` or
+/// `Intel 80386` from `PE32 executable (console) Intel 80386, for MS Windows`.
+fn executable_arch(description: &str) -> Option<String> {
+    let mut fields = description.split(", ");
+    let first = fields.next()?;
+    let arch = if first.starts_with("ELF ") {
+        fields.next()
+    } else if first.starts_with("PE32") {
+        // The subsystem is in parentheses, e.g. `(DLL) (GUI)`, the architecture follows, or
+        // is the next field as in `PE32+ executable for EFI (application), x86-64`
+        match first.rsplit(") ").next().filter(|&arch| arch != first) {
+            Some(arch) => Some(arch.strip_suffix(" Mono/.Net assembly").unwrap_or(arch)),
+            None => fields.next().filter(|field| !field.starts_with("for ")),
+        }
+    } else if first.starts_with("Mach-O ") && !first.starts_with("Mach-O universal") {
+        // e.g. `Mach-O 64-bit arm64 executable`, but `Mach-O i386 executable` for 32 bits
+        first.split(' ').skip(1).find(|&token| token != "64-bit")
+    } else {
+        None
+    };
+    arch.map(|arch| arch.trim().to_string()).filter(|arch| !arch.is_empty())
+}
+
+/// Returns the warnings among what `libmagic` printed to `stderr`
+#[cfg(unix)]
+fn warnings(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter(|line| line.contains("Warning: "))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Appends the warnings among the captured `output` to the description of `err`
+#[cfg(unix)]
+fn with_warnings(mut err: FileMagicError, output: &[u8]) -> FileMagicError {
+    for warning in warnings(output) {
+        err.desc.push('\n');
+        err.desc.push_str(&warning);
+    }
+    err
+}
+
+/// The most bytes a result of `libmagic` is expected to have, including `Flags::CONTINUE`
+const MAX_RESULT_LEN: usize = 1024 * 1024;
+
+/// Returns the bytes of the C string `str` returned by `libmagic`
+///
+/// Unlike `CStr::from_ptr` this looks at `MAX_RESULT_LEN` bytes at most, so a string which
+/// lacks its terminating NUL fails instead of being read past its end.
+unsafe fn result_bytes<'a>(str: *const c_char) -> Result<&'a [u8], FileMagicError> {
+    let len = libc::strnlen(str, MAX_RESULT_LEN);
+    if len == MAX_RESULT_LEN {
+        Err(FileMagicError::new(
+            format!("result of libmagic is not terminated within {} bytes", MAX_RESULT_LEN),
+            libc::EOVERFLOW,
+        ))
+    } else {
+        Ok(std::slice::from_raw_parts(str as *const u8, len))
     }
 }
 
+/// Returns the C string `str` returned by `libmagic`, which must be valid UTF-8
+unsafe fn result_string(str: *const c_char) -> Result<String, FileMagicError> {
+    let bytes = result_bytes(str)?;
+    String::from_utf8(bytes.to_vec()).map_err(|e| FileMagicError::new(e.to_string(), libc::EILSEQ))
+}
+
+/// Reads at most `max_bytes` from `reader`, fewer if it runs out of data before
+fn read_prefix<R: Read>(reader: R, max_bytes: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.take(max_bytes as u64).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// How completely the databases were loaded, see `Magic::load_with_status`
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LoadStatus {
+    /// All entries of all databases were loaded
+    Full,
+    /// Some entries were skipped, with the warnings `libmagic` printed about them
+    Partial(Vec<String>),
+    /// Nothing could be loaded
+    Failed(FileMagicError),
+}
+
+/// What produced a result of `libmagic`, see `Magic::match_source`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MatchSource {
+    /// One of the tests built into `libmagic`, e.g. for text or compressed files
+    Builtin,
+    /// An entry of the loaded databases
+    Database,
+}
+
 /// The error type used in this crate
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct FileMagicError {
     pub desc: String,
+    /// The `errno` reported by `libmagic` at the time of the failure, `0` if none
+    pub errno: c_int,
+}
+
+impl FileMagicError {
+    /// Creates a new error from a textual description and an `errno`
+    pub fn new<S: Into<String>>(desc: S, errno: c_int) -> FileMagicError {
+        FileMagicError {
+            desc: desc.into(),
+            errno,
+        }
+    }
+
+    /// Returns the textual description of the error
+    pub fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    /// Returns the `errno` captured along with the error, `0` if none
+    pub fn errno(&self) -> c_int {
+        self.errno
+    }
 }
 
 impl error::Error for FileMagicError {
@@ -79,44 +346,160 @@ impl Display for FileMagicError {
     }
 }
 
+impl From<io::Error> for FileMagicError {
+    fn from(err: io::Error) -> FileMagicError {
+        FileMagicError::new(err.to_string(), err.raw_os_error().unwrap_or(0))
+    }
+}
+
+/// The textual description, MIME type and encoding of some contents, see `Magic::classify`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Classification {
+    /// The textual description, e.g. `PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced`
+    pub description: String,
+    /// The MIME type, e.g. `image/png`
+    pub mime_type: String,
+    /// The MIME encoding, e.g. `binary`
+    pub encoding: String,
+}
+
+/// The databases loaded into a `Magic`, to be able to load them again
+enum Databases {
+    /// Database files, none for the default database
+    Files(Vec<PathBuf>),
+    /// Compiled databases in memory, which `libmagic` keeps using without copying them
+    Buffers(Vec<Vec<u8>>),
+}
+
 /// Configuration of which `Flags` and magic databases to use
 pub struct Magic {
     magic: *const api::Magic,
+    /// The flags set last, `libmagic` may add flags of its own, e.g. `CHECK` during `load()`
+    flags: Cell<Flags>,
+    /// The databases loaded last, `None` if no databases were loaded yet
+    databases: RefCell<Option<Databases>>,
+    /// The error of the last failed operation, see `last_error()`
+    last_error: RefCell<Option<FileMagicError>>,
+    /// What to report instead of generic fallbacks, see `set_unknown_as()`
+    unknown_as: RefCell<Option<String>>,
 }
 
+// A cookie may be moved to another thread, it just must not be used concurrently
+unsafe impl Send for Magic {}
+
 impl Drop for Magic {
     /// Closes the magic database and deallocates any resources used
     fn drop(&mut self) {
-        unsafe { api::magic_close(self.magic) }
+        if !self.magic.is_null() {
+            unsafe { api::magic_close(self.magic) }
+            self.magic = ptr::null();
+        }
     }
 }
 
 impl Magic {
-    fn last_error(&self) -> Option<FileMagicError> {
+    fn magic_error(&self) -> Option<FileMagicError> {
         let cookie = self.magic;
 
+        // Both values are read right away, before any other call on the cookie
+        // gets a chance to overwrite them
         unsafe {
             let e = api::magic_error(cookie);
+            let errno = api::magic_errno(cookie);
             if e.is_null() {
                 None
             } else {
-                let slice = CStr::from_ptr(e).to_bytes();
-                Some(self::FileMagicError {
-                    desc: str::from_utf8(slice).unwrap().to_string(),
+                Some(match result_bytes(e) {
+                    Ok(desc) => self::FileMagicError::new(String::from_utf8_lossy(desc), errno),
+                    Err(err) => err,
                 })
             }
         }
     }
 
     fn magic_failure(&self) -> FileMagicError {
-        match self.last_error() {
+        let err = match self.magic_error() {
             Some(e) => e,
-            None => self::FileMagicError {
-                desc: "unknown error".to_string(),
-            },
+            None => self::FileMagicError::new("unknown error", self.errno()),
+        };
+        self.record(err)
+    }
+
+    /// Keeps a copy of `err` for `last_error()`
+    fn record(&self, err: FileMagicError) -> FileMagicError {
+        *self.last_error.borrow_mut() = Some(err.clone());
+        err
+    }
+
+    /// Runs `f` with `flags` set, restoring the previous flags afterwards
+    ///
+    /// Fails without running `f` if `libmagic` rejects the `flags`, instead of letting `f`
+    /// run with the wrong ones. The previous flags are restored even if `f` fails.
+    fn with_flags<T, F>(&self, flags: Flags, f: F) -> Result<T, FileMagicError>
+    where
+        F: FnOnce(&Magic) -> Result<T, FileMagicError>,
+    {
+        let previous = self.current_flags();
+        self.try_set_flags(flags)?;
+        let ret = f(self);
+        let restored = self.try_set_flags(previous);
+        let ret = ret?;
+        restored.map(|_| ret)
+    }
+
+    /// Runs `f` with generic fallbacks reported as is, e.g. to parse the results
+    fn verbatim<T, F: FnOnce(&Magic) -> T>(&self, f: F) -> T {
+        let previous = self.unknown_as.borrow_mut().take();
+        let ret = f(self);
+        *self.unknown_as.borrow_mut() = previous;
+        ret
+    }
+
+    /// Returns `result`, or the value set with `set_unknown_as()` if it is a generic fallback
+    fn substitute(&self, result: String) -> String {
+        match *self.unknown_as.borrow() {
+            Some(ref value) if is_generic(&result) => value.clone(),
+            _ => result,
+        }
+    }
+
+    /// Returns the current flags with the output switched to `output`, e.g. `Flags::MIME`
+    ///
+    /// `Flags::NONE` switches the output to the textual description. `Flags::CONTINUE` and
+    /// `Flags::DEBUG` are cleared as well, so that there is a single result to interpret.
+    fn output_flags(&self, output: Flags) -> Flags {
+        let others = Flags::MIME | Flags::APPLE | Flags::EXTENSION | Flags::CONTINUE | Flags::DEBUG;
+        (self.current_flags() - others) | output
+    }
+
+    /// Classifies the `filename` with the output temporarily switched to `output`
+    fn file_with_output<P: AsRef<Path>>(&self, filename: P, output: Flags) -> Result<String, FileMagicError> {
+        self.verbatim(|magic| magic.with_flags(magic.output_flags(output), |magic| magic.file(filename)))
+    }
+
+    /// Fails unless a database was loaded, instead of letting `libmagic` fail obscurely
+    fn ensure_loaded(&self) -> Result<(), FileMagicError> {
+        if self.is_loaded() {
+            Ok(())
+        } else {
+            Err(self.record(self::FileMagicError::new("no magic database loaded", 0)))
         }
     }
 
+    /// Returns the error of the last operation that failed, even if others followed
+    ///
+    /// Unlike `error()`, which `libmagic` overwrites with every call, this is kept until
+    /// the next failure. It covers the failures of `libmagic` itself and missing databases,
+    /// not e.g. files this crate failed to open or read.
+    pub fn last_error(&self) -> Option<FileMagicError> {
+        self.last_error.borrow().clone()
+    }
+
+    /// Returns whether a database was loaded successfully, e.g. with `load()`
+    pub fn is_loaded(&self) -> bool {
+        self.databases.borrow().is_some()
+    }
+
     /// Returns a textual explanation of the last error, if any
     ///
     /// You should not need to call this, since you can use the `FileMagicError` in
@@ -128,122 +511,1049 @@ impl Magic {
             if str.is_null() {
                 None
             } else {
-                let slice = CStr::from_ptr(str).to_bytes();
-                Some(str::from_utf8(slice).unwrap().to_string())
+                result_bytes(str).ok().map(|desc| String::from_utf8_lossy(desc).into_owned())
             }
         }
     }
 
+    /// Returns the `errno` of the last operating system error, if any
+    ///
+    /// This allows to tell apart e.g. a missing file (`ENOENT`) from a file that
+    /// could not be classified, without having to match on the error message.
+    pub fn errno(&self) -> c_int {
+        unsafe { api::magic_errno(self.magic) }
+    }
+
     /// Returns a textual description of the contents of the `filename`
+    ///
+    /// Fails with `EILSEQ` if the description is not valid UTF-8, use `file_bytes()`
+    /// to get it verbatim.
     pub fn file<P: AsRef<Path>>(&self, filename: P) -> Result<String, FileMagicError> {
+        let bytes = self.file_bytes(filename)?;
+        let description = String::from_utf8(bytes)
+            .map_err(|e| FileMagicError::new(e.to_string(), libc::EILSEQ))?;
+        Ok(self.substitute(description))
+    }
+
+    /// Returns the textual description of the contents of the `filename` with the flags used
+    ///
+    /// The flags are those `libmagic` classified with, read right before, so that the result
+    /// can be reproduced. Unlike `get_flags()` bits unknown to this crate are kept.
+    pub fn classify_with_context<P: AsRef<Path>>(&self, filename: P) -> Result<(Flags, String), FileMagicError> {
+        let flags = raw_flags(unsafe { api::magic_getflags(self.magic) });
+        Ok((flags, self.file(filename)?))
+    }
+
+    /// Returns the textual description of the contents of the `filename` as raw bytes
+    ///
+    /// Unlike `file()` this does not require the description to be valid UTF-8, which
+    /// `libmagic` does not guarantee, e.g. with `Flags::RAW` and exotic files.
+    pub fn file_bytes<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, FileMagicError> {
+        let mut out = Vec::new();
+        self.file_into(filename, &mut out)?;
+        Ok(out)
+    }
+
+    /// Writes the textual description of the contents of the `filename` into `out`
+    ///
+    /// `out` is cleared first, reusing it across calls avoids allocating for every file.
+    /// The description is not required to be valid UTF-8, like with `file_bytes()`.
+    pub fn file_into<P: AsRef<Path>>(&self, filename: P, out: &mut Vec<u8>) -> Result<(), FileMagicError> {
+        out.clear();
+        self.ensure_loaded()?;
         let cookie = self.magic;
-        let f = CString::new(filename.as_ref().to_string_lossy().into_owned())
-            .unwrap()
-            .into_raw();
+        let f = path_to_cstring(filename.as_ref())?;
         unsafe {
-            let str = api::magic_file(cookie, f);
+            let str = api::magic_file(cookie, f.as_ptr());
             if str.is_null() {
                 Err(self.magic_failure())
             } else {
-                let slice = CStr::from_ptr(str).to_bytes();
-                Ok(str::from_utf8(slice).unwrap().to_string())
+                out.extend_from_slice(result_bytes(str)?);
+                Ok(())
             }
         }
     }
 
+    /// Returns the textual descriptions of all matches for the contents of the `filename`
+    ///
+    /// The matches are ordered as `libmagic` ranks them, strongest first. `Flags::CONTINUE`
+    /// is set for the duration of the call.
+    pub fn file_all<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<String>, FileMagicError> {
+        let flags = self.current_flags() | Flags::CONTINUE;
+        let all = self.with_flags(flags, |magic| magic.file(filename))?;
+        Ok(split_matches(&all))
+    }
+
+    /// Returns all matches for the contents of the `filename` with their rank
+    ///
+    /// This is `file_all()` with each match numbered, `0` being the strongest match.
+    /// `libmagic` doesn't expose the strength of the matches themselves.
+    pub fn ranked_matches<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<(usize, String)>, FileMagicError> {
+        Ok(self.file_all(filename)?.into_iter().enumerate().collect())
+    }
+
+    /// Returns the MIME type and charset of the contents of the `filename`
+    ///
+    /// `Flags::MIME` is set for the duration of the call.
+    pub fn mime<P: AsRef<Path>>(&self, filename: P) -> Result<Mime, FileMagicError> {
+        let mime = self.file_with_output(filename, Flags::MIME)?;
+        Ok(Mime::parse(&mime))
+    }
+
+    /// Returns whether `libmagic` recognizes the contents of the `filename` as anything specific
+    ///
+    /// `Flags::MIME_TYPE` is set for the duration of the call. Contents are not recognized if
+    /// their MIME type is one of the `GENERIC_MIME_TYPES`.
+    pub fn is_recognized<P: AsRef<Path>>(&self, filename: P) -> Result<bool, FileMagicError> {
+        let mime_type = self.file_with_output(filename, Flags::MIME_TYPE)?;
+        Ok(!GENERIC_MIME_TYPES.contains(&mime_type.as_str()))
+    }
+
+    /// Returns whether the `filename` is empty
+    ///
+    /// The size of regular files is checked without classifying them. Anything else, e.g. a
+    /// device, is read and classified with `Flags::DEVICES` and `Flags::MIME_TYPE` set, which
+    /// reports empty contents as `application/x-empty` or `inode/x-empty` depending on the
+    /// version of `libmagic`.
+    pub fn is_empty_file<P: AsRef<Path>>(&self, filename: P) -> Result<bool, FileMagicError> {
+        let filename = filename.as_ref();
+        let metadata = std::fs::metadata(filename)?;
+        if metadata.is_file() {
+            return Ok(metadata.len() == 0);
+        }
+        let flags = self.output_flags(Flags::MIME_TYPE) | Flags::DEVICES;
+        let mime_type = self.verbatim(|magic| magic.with_flags(flags, |magic| magic.file(filename)))?;
+        Ok(mime_type == "application/x-empty" || mime_type == "inode/x-empty")
+    }
+
+    /// Returns whether the `buffer` is empty, the counterpart of `is_empty_file()`
+    ///
+    /// No classification is needed, `libmagic` describes exactly the empty buffer as `empty`.
+    pub fn is_empty_buffer<B: AsRef<[u8]>>(&self, buffer: B) -> bool {
+        buffer.as_ref().is_empty()
+    }
+
+    /// Reports `value` instead of the generic fallbacks of `libmagic`, if it is set
+    ///
+    /// Results of `file()`, `buffer()` and `descriptor()` that are one of the
+    /// `GENERIC_DESCRIPTIONS` or `GENERIC_MIME_TYPES` are replaced by `value`, e.g. an empty
+    /// string to treat them as unknown. `None` reports them as is again. The methods that
+    /// interpret results, e.g. `mime()` or `is_recognized()`, always see them as is, and so
+    /// do the layered classifications like `inner_type()` and `match_source()`.
+    pub fn set_unknown_as(&self, value: Option<String>) {
+        *self.unknown_as.borrow_mut() = value;
+    }
+
+    /// Returns whether the MIME type of the contents of the `filename` is one of `allowed`
+    ///
+    /// `Flags::MIME_TYPE` is set for the duration of the call. The `allowed` types may use
+    /// wildcards, e.g. `image/*` or `*/*`.
+    pub fn matches_mime<P: AsRef<Path>>(&self, filename: P, allowed: &[&str]) -> Result<bool, FileMagicError> {
+        let mime_type = self.file_with_output(filename, Flags::MIME_TYPE)?;
+        Ok(allowed.iter().any(|pattern| mime::matches(&mime_type, pattern)))
+    }
+
+    /// Returns whether the contents of the files `a` and `b` have the same MIME type
+    ///
+    /// `Flags::MIME_TYPE` is set for the duration of the call, the charsets are ignored.
+    pub fn same_type<P: AsRef<Path>, Q: AsRef<Path>>(&self, a: P, b: Q) -> Result<bool, FileMagicError> {
+        let a = self.file_with_output(a, Flags::MIME_TYPE)?;
+        let b = self.file_with_output(b, Flags::MIME_TYPE)?;
+        Ok(a == b)
+    }
+
+    /// Returns whether the contents of the `filename` are an archive of other files
+    ///
+    /// This is `matches_mime()` with the `ARCHIVE_MIME_TYPES`, call it with a list of your
+    /// own to decide on other types.
+    pub fn is_archive<P: AsRef<Path>>(&self, filename: P) -> Result<bool, FileMagicError> {
+        self.matches_mime(filename, ARCHIVE_MIME_TYPES)
+    }
+
+    /// Returns the MIME type of the contents of the `filename` as a `CommonMime`
+    ///
+    /// `Flags::MIME_TYPE` is set for the duration of the call. Uncommon MIME types are
+    /// returned as `CommonMime::Other`.
+    pub fn common_mime<P: AsRef<Path>>(&self, filename: P) -> Result<CommonMime, FileMagicError> {
+        let mime_type = self.file_with_output(filename, Flags::MIME_TYPE)?;
+        Ok(CommonMime::from(mime_type.as_str()))
+    }
+
+    /// Returns the Apple creator and type codes of the `filename`, e.g. `("GKON", "TEXT")`
+    ///
+    /// `Flags::APPLE` is set for the duration of the call. Returns `None` if `libmagic`
+    /// reports less than the two four character codes; unknown codes are reported as `UNKN`.
+    pub fn apple<P: AsRef<Path>>(&self, filename: P) -> Result<Option<(String, String)>, FileMagicError> {
+        let codes = self.file_with_output(filename, Flags::APPLE)?;
+        match (codes.get(..4), codes.get(4..8)) {
+            (Some(creator), Some(type_)) => Ok(Some((creator.to_string(), type_.to_string()))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the textual description, MIME type and encoding of the contents of the `filename`
+    ///
+    /// `libmagic` can't report both in one pass, so the same cookie classifies twice with
+    /// the flags switched in between.
+    pub fn classify<P: AsRef<Path>>(&self, filename: P) -> Result<Classification, FileMagicError> {
+        let filename = filename.as_ref();
+        let description = self.file_with_output(filename, Flags::NONE)?;
+        let mime = self.mime(filename)?;
+        Ok(Classification {
+            description,
+            mime_type: format!("{}/{}", mime.type_, mime.subtype),
+            encoding: mime.charset.unwrap_or_default(),
+        })
+    }
+
+    /// Returns the file extensions commonly used for the contents of the `filename`
+    ///
+    /// `Flags::EXTENSION` is set for the duration of the call. The extensions are in the
+    /// order `libmagic` reports them, none if it doesn't know of any.
+    pub fn extensions<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<String>, FileMagicError> {
+        let extensions = self.file_with_output(filename, Flags::EXTENSION)?;
+        if extensions == "???" {
+            return Ok(Vec::new());
+        }
+        Ok(extensions.split('/').map(|ext| ext.to_string()).collect())
+    }
+
+    /// Returns all the file extensions `libmagic` suggests for the contents of the `filename`
+    ///
+    /// This is `extensions()`, e.g. to offer a choice of extensions where
+    /// `preferred_extension()` picks the first.
+    pub fn extension_candidates<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<String>, FileMagicError> {
+        self.extensions(filename)
+    }
+
+    /// Returns the preferred file extension for the contents of the `filename`
+    ///
+    /// This is the first of `extensions()`, e.g. to name a file after its contents.
+    pub fn preferred_extension<P: AsRef<Path>>(&self, filename: P) -> Result<Option<String>, FileMagicError> {
+        Ok(self.extensions(filename)?.into_iter().next())
+    }
+
+    /// Returns the character encoding of the contents of the `filename`
+    ///
+    /// `Flags::MIME_ENCODING` is set for the duration of the call. Files which are not text
+    /// are `Encoding::Binary`.
+    pub fn encoding<P: AsRef<Path>>(&self, filename: P) -> Result<Encoding, FileMagicError> {
+        let charset = self.file_with_output(filename, Flags::MIME_ENCODING)?;
+        Ok(Encoding::from(charset.as_str()))
+    }
+
+    /// Returns whether the contents of the `filename` are text rather than binary
+    ///
+    /// `Flags::MIME_ENCODING` is set for the duration of the call, any encoding but
+    /// `binary` counts as text.
+    pub fn is_text<P: AsRef<Path>>(&self, filename: P) -> Result<bool, FileMagicError> {
+        let encoding = self.file_with_output(filename, Flags::MIME_ENCODING)?;
+        Ok(encoding != "binary")
+    }
+
+    /// Returns the architecture the executable `filename` is built for, e.g. `x86-64`
+    ///
+    /// The output is switched to the textual description for the duration of the call, as
+    /// with `Flags::NONE`, and the architecture is taken from the way `libmagic` describes
+    /// ELF, PE and Mach-O files. Other files have no architecture.
+    pub fn executable_arch<P: AsRef<Path>>(&self, filename: P) -> Result<Option<String>, FileMagicError> {
+        let description = self.file_with_output(filename, Flags::NONE)?;
+        Ok(executable_arch(&description))
+    }
+
+    /// Returns whether the description of the `filename` comes from a builtin test or a database
+    ///
+    /// `libmagic` doesn't tell, so this classifies once more with `Flags::NO_CHECK_SOFT` set,
+    /// i.e. without consulting the databases. The description came from the databases if the
+    /// results differ. Contents nothing matches are reported as `MatchSource::Builtin`.
+    pub fn match_source<P: AsRef<Path>>(&self, filename: P) -> Result<MatchSource, FileMagicError> {
+        let filename = filename.as_ref();
+        let flags = self.current_flags();
+        let (with, without) = self.verbatim(|magic| {
+            let with = magic.file(filename)?;
+            let without = magic.with_flags(flags | Flags::NO_CHECK_SOFT, |magic| magic.file(filename))?;
+            Ok::<_, FileMagicError>((with, without))
+        })?;
+        Ok(if with == without { MatchSource::Builtin } else { MatchSource::Database })
+    }
+
+    /// Returns whether `libmagic` looks through a compression layer of the `filename`
+    ///
+    /// Classifies once with and once without `Flags::COMPRESS`, the file was decompressed
+    /// if the results differ.
+    pub fn was_decompressed<P: AsRef<Path>>(&self, filename: P) -> Result<bool, FileMagicError> {
+        let filename = filename.as_ref();
+        let flags = self.current_flags();
+        self.verbatim(|magic| {
+            let with = magic.with_flags(flags | Flags::COMPRESS, |magic| magic.file(filename))?;
+            let without = magic.with_flags(flags - Flags::COMPRESS, |magic| magic.file(filename))?;
+            Ok(with != without)
+        })
+    }
+
+    /// Returns the textual description of the decompressed contents of the `filename`
+    ///
+    /// `Flags::COMPRESS` and `Flags::COMPRESS_TRANSP` are set for the duration of the
+    /// call, so the compression itself is not reported. Uncompressed files are described
+    /// as usual.
+    pub fn inner_type<P: AsRef<Path>>(&self, filename: P) -> Result<String, FileMagicError> {
+        let flags = (self.current_flags() - Flags::NO_CHECK_COMPRESS)
+            | Flags::COMPRESS
+            | Flags::COMPRESS_TRANSP;
+        self.verbatim(|magic| magic.with_flags(flags, |magic| magic.file(filename)))
+    }
+
+    /// Returns the textual description of the `filename` without looking inside it
+    ///
+    /// `Flags::NO_CHECK_COMPRESS` is set and `Flags::COMPRESS` is cleared for the duration
+    /// of the call, so compressed files are described by their compression format.
+    pub fn container_type<P: AsRef<Path>>(&self, filename: P) -> Result<String, FileMagicError> {
+        let flags = (self.current_flags() - Flags::COMPRESS - Flags::COMPRESS_TRANSP)
+            | Flags::NO_CHECK_COMPRESS;
+        self.verbatim(|magic| magic.with_flags(flags, |magic| magic.file(filename)))
+    }
+
+    /// Returns the compression format of the `filename`, e.g. `gzip`, if it is compressed
+    ///
+    /// This looks at the outer layer only, like `container_type()`, and recognizes gzip,
+    /// bzip2, xz, zstd, lzma, lzip, lz4 and compress. Archives like zip are not considered
+    /// compressed files.
+    pub fn compression<P: AsRef<Path>>(&self, filename: P) -> Result<Option<String>, FileMagicError> {
+        let flags = self.output_flags(Flags::MIME_TYPE);
+        let mime = self.with_flags(flags, |magic| magic.container_type(filename))?;
+        Ok(COMPRESSION_MIME_TYPES
+            .iter()
+            .find(|&&(mime_type, _)| mime_type == mime)
+            .map(|&(_, name)| name.to_string()))
+    }
+
+    /// Returns the target of the symlink `filename`, if it is one
+    ///
+    /// The symlink is not followed, `Flags::SYMLINK` is cleared and `Flags::RAW` is set for
+    /// the duration of the call so that the target is reported verbatim. Broken symlinks
+    /// have a target as well, `Flags::ERROR` is cleared so they are not reported as errors.
+    pub fn symlink_target<P: AsRef<Path>>(&self, filename: P) -> Result<Option<String>, FileMagicError> {
+        let filename = filename.as_ref();
+        // Without `Flags::ERROR` libmagic would describe a missing file instead of failing
+        std::fs::symlink_metadata(filename)?;
+        let flags = (self.output_flags(Flags::NONE) - Flags::SYMLINK - Flags::ERROR) | Flags::RAW;
+        let description = self.with_flags(flags, |magic| magic.file(filename))?;
+        let target = description
+            .strip_prefix("symbolic link to ")
+            .or_else(|| description.strip_prefix("broken symbolic link to "));
+        Ok(target.map(|target| target.to_string()))
+    }
+
     /// Returns a textual description of the contents of the `buffer`
-    pub fn buffer(&self, buffer: &[u8]) -> Result<String, FileMagicError> {
+    ///
+    /// An empty `buffer` is passed to `libmagic` as a valid pointer with a length of zero,
+    /// which it describes as `empty`. Anything that can be viewed as bytes is accepted,
+    /// e.g. `&[u8]`, `Vec<u8>`, `&str` or `String`.
+    pub fn buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<String, FileMagicError> {
+        self.ensure_loaded()?;
+        let buffer = buffer.as_ref();
         let buffer_len = buffer.len() as size_t;
-        let pbuffer = buffer.as_ptr();
+        // An empty slice may point anywhere, so hand out a real byte instead
+        let pbuffer = if buffer.is_empty() { EMPTY.as_ptr() } else { buffer.as_ptr() };
         unsafe {
             let str = api::magic_buffer(self.magic, pbuffer, buffer_len);
             if str.is_null() {
                 Err(self.magic_failure())
             } else {
-                let slice = CStr::from_ptr(str).to_bytes();
-                Ok(str::from_utf8(slice).unwrap().to_string())
+                result_string(str).map(|result| self.substitute(result))
+            }
+        }
+    }
+
+    /// Returns the MIME type and charset of the contents of the `buffer`
+    ///
+    /// `Flags::MIME` is set for the duration of the call, like with `mime()`.
+    pub fn buffer_mime<B: AsRef<[u8]>>(&self, buffer: B) -> Result<Mime, FileMagicError> {
+        let flags = self.output_flags(Flags::MIME);
+        let mime = self.verbatim(|magic| magic.with_flags(flags, |magic| magic.buffer(buffer)))?;
+        Ok(Mime::parse(&mime))
+    }
+
+    /// Returns a textual description of the `len` bytes of `buffer` starting at `offset`
+    ///
+    /// Useful to classify data embedded in a larger buffer without copying it. Fails if the
+    /// range is not within `buffer`.
+    pub fn buffer_at(&self, buffer: &[u8], offset: usize, len: usize) -> Result<String, FileMagicError> {
+        let range = offset.checked_add(len).and_then(|end| buffer.get(offset..end));
+        match range {
+            Some(range) => self.buffer(range),
+            None => Err(self::FileMagicError::new(
+                format!(
+                    "range of {} bytes at offset {} is out of bounds for a buffer of {} bytes",
+                    len,
+                    offset,
+                    buffer.len()
+                ),
+                libc::EINVAL,
+            )),
+        }
+    }
+
+    /// Returns a textual description of the contents of the owned `data`
+    ///
+    /// Behaves exactly like `buffer()`, an empty `data` is classified by `libmagic` as well.
+    pub fn identify_bytes(&self, data: Vec<u8>) -> Result<String, FileMagicError> {
+        self.buffer(data)
+    }
+
+    /// Returns a textual description of all of the contents of `cursor`
+    ///
+    /// This is `buffer(cursor.get_ref())`, the position of the `cursor` doesn't matter.
+    pub fn cursor<T: AsRef<[u8]>>(&self, cursor: &io::Cursor<T>) -> Result<String, FileMagicError> {
+        self.buffer(cursor.get_ref())
+    }
+
+    /// Returns a textual description of the first `max_bytes` read from `reader`
+    ///
+    /// At most `max_bytes` are read, fewer if the `reader` runs out of data before.
+    pub fn reader<R: Read>(&self, reader: R, max_bytes: usize) -> Result<String, FileMagicError> {
+        self.buffer(read_prefix(reader, max_bytes)?)
+    }
+
+    /// Returns a reader passing through `reader`, classifying its first `max_bytes` on the way
+    ///
+    /// Unlike `reader()` nothing is lost, the bytes read for classifying it are replayed.
+    /// The description is available from `ClassifyingReader::classified_as()` after the
+    /// first read.
+    pub fn classifying_reader<'a, R: Read>(&'a self, reader: R, max_bytes: usize) -> ClassifyingReader<'a, R> {
+        ClassifyingReader::new(self, reader, max_bytes)
+    }
+
+    /// Returns the MIME type and charset of the first `max_bytes` read from `reader`
+    ///
+    /// This is `reader()` with `Flags::MIME` set for the duration of the call, like
+    /// `buffer_mime()`.
+    pub fn reader_mime<R: Read>(&self, reader: R, max_bytes: usize) -> Result<Mime, FileMagicError> {
+        self.buffer_mime(read_prefix(reader, max_bytes)?)
+    }
+
+    /// Returns a textual description of the first `max_bytes` of the `filename`
+    ///
+    /// The prefix is read by this crate and classified like with `buffer()`, so no more
+    /// than `max_bytes` are ever read, e.g. to bound the I/O on network filesystems.
+    pub fn file_prefix<P: AsRef<Path>>(&self, filename: P, max_bytes: usize) -> Result<String, FileMagicError> {
+        self.reader(std::fs::File::open(filename)?, max_bytes)
+    }
+
+    /// Returns a textual description of the first `header_len` bytes of the `filename`
+    ///
+    /// Only that part of the file is memory-mapped and classified like with `buffer()`,
+    /// so large files are neither read nor mapped completely.
+    #[cfg(unix)]
+    pub fn mmap_file<P: AsRef<Path>>(&self, filename: P, header_len: usize) -> Result<String, FileMagicError> {
+        let file = std::fs::File::open(filename)?;
+        let len = std::cmp::min(header_len as u64, file.metadata()?.len()) as usize;
+        if len == 0 {
+            // Empty mappings are not allowed
+            return self.buffer(b"");
+        }
+        unsafe {
+            let map = libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            );
+            if map == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error().into());
+            }
+            let result = self.buffer(std::slice::from_raw_parts(map as *const u8, len));
+            libc::munmap(map, len);
+            result
+        }
+    }
+
+    /// Returns the members of the tar archive `filename` with their descriptions
+    ///
+    /// The contents of each file are classified like with `buffer()`, reading at most
+    /// `Param::BytesMax` bytes of each. Directories, links and special files are reported
+    /// as such instead, e.g. `directory` or `symbolic link to target`.
+    pub fn tar_members<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<(String, String)>, FileMagicError> {
+        let max_bytes = self.get_param(Param::BytesMax).unwrap_or(Param::BytesMax.default_value());
+        let mut archive = io::BufReader::new(std::fs::File::open(filename)?);
+        let mut members = Vec::new();
+        while let Some(member) = tar::next_member(&mut archive, max_bytes as u64)? {
+            let description = match member.kind {
+                tar::Kind::File => self.buffer(&member.contents)?,
+                tar::Kind::Directory => "directory".to_string(),
+                tar::Kind::Symlink(target) => format!("symbolic link to {}", target),
+                tar::Kind::HardLink(target) => format!("hard link to {}", target),
+                tar::Kind::Other => "special file".to_string(),
+            };
+            members.push((member.name, description));
+        }
+        Ok(members)
+    }
+
+    /// Returns a textual description of the contents of the open file descriptor `fd`
+    ///
+    /// The descriptor is neither closed nor otherwise taken over by `libmagic`,
+    /// it stays owned by the caller.
+    ///
+    /// Pipes and sockets are supported as well, they are never rewound. From those only
+    /// a prefix of at most `Param::BytesMax` bytes is consumed, the rest can still be read.
+    pub fn descriptor(&self, fd: c_int) -> Result<String, FileMagicError> {
+        self.ensure_loaded()?;
+        unsafe {
+            let str = api::magic_descriptor(self.magic, fd);
+            if str.is_null() {
+                Err(self.magic_failure())
+            } else {
+                result_string(str).map(|result| self.substitute(result))
             }
         }
     }
 
+    /// Returns a textual description of the contents of the standard input
+    ///
+    /// This is `descriptor()` on file descriptor 0, the library equivalent of `file -`.
+    /// An empty standard input is described as `empty`.
+    pub fn stdin(&self) -> Result<String, FileMagicError> {
+        self.descriptor(0)
+    }
+
+    /// Returns an iterator over the entries of the directory `dir` and their descriptions
+    ///
+    /// Subdirectories are only descended into if `recursive` is set, symlinks to
+    /// directories never are. Whether symlinks themselves are followed when classifying
+    /// depends on `Flags::SYMLINK`.
+    pub fn walk_dir<P: AsRef<Path>>(&self, dir: P, recursive: bool) -> WalkDir<'_> {
+        WalkDir::new(self, dir.as_ref(), recursive)
+    }
+
+    /// Returns an iterator over `paths` and their descriptions, in the order given
+    ///
+    /// Each path is only classified when its element is requested, so stopping early
+    /// skips the rest. `error()` and `errno()` reflect only the most recently yielded
+    /// element, the error of each element is part of its result.
+    pub fn files<'a, P: AsRef<Path>>(&'a self, paths: &'a [P]) -> Files<'a, P> {
+        Files::new(self, paths)
+    }
+
+    /// Classifies all `paths`, returning each path along with its description or error
+    ///
+    /// A file that fails to classify doesn't stop the batch, this is `files()` collected.
+    /// Each error is a snapshot taken right after its path failed.
+    pub fn classify_batch<P: AsRef<Path>>(&self, paths: &[P]) -> Vec<(PathBuf, Result<String, FileMagicError>)> {
+        self.files(paths).map(|(path, result)| (path.as_ref().to_path_buf(), result)).collect()
+    }
+
+    /// Returns the MIME type and charset of the contents of the open file `handle`
+    ///
+    /// `Flags::MIME` is set for the duration of the call. `libmagic` neither closes the
+    /// descriptor nor moves its file offset, so the `handle` can still be read afterwards.
+    #[cfg(unix)]
+    pub fn fd_mime<T: AsRawFd>(&self, handle: &T) -> Result<Mime, FileMagicError> {
+        let fd = handle.as_raw_fd();
+        let flags = self.output_flags(Flags::MIME);
+        let mime = self.verbatim(|magic| magic.with_flags(flags, |magic| magic.descriptor(fd)))?;
+        Ok(Mime::parse(&mime))
+    }
+
     /// Check the validity of entries in the database `filenames`
     pub fn check<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<(), FileMagicError> {
         let cookie = self.magic;
-        let db_filenames = db_filenames(filenames);
+        let db_filenames = db_filenames(filenames)?;
         let ret;
 
         unsafe {
-            ret = api::magic_check(cookie, db_filenames);
+            ret = api::magic_check(cookie, db_filenames_ptr(&db_filenames));
         }
         if 0 == ret { Ok(()) } else { Err(self.magic_failure()) }
     }
 
+    /// Check the validity of entries in the database `filenames`, reporting what is wrong
+    ///
+    /// This is `check()`, but on failure the warnings `libmagic` prints to `stderr`, e.g.
+    /// about unparsable lines, are appended to the returned error. Note that `stderr` is
+    /// redirected for the whole process during the call.
+    #[cfg(unix)]
+    pub fn check_verbose<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<(), FileMagicError> {
+        let (ret, output) = capture::capture(libc::STDERR_FILENO, || self.check(filenames))?;
+        ret.map_err(|e| with_warnings(e, &output))
+    }
+
+    /// Runs `f` with `Flags::CHECK` set, collecting the warnings `libmagic` prints instead
+    ///
+    /// Returns the result of `f` along with the warnings, e.g. about malformed entries
+    /// while loading a database in `f`. Note that `stderr` is redirected for the whole
+    /// process during the call.
+    #[cfg(unix)]
+    pub fn collect_warnings<T, F: FnOnce(&Magic) -> T>(&self, f: F) -> Result<(T, Vec<String>), FileMagicError> {
+        let flags = self.current_flags() | Flags::CHECK;
+        let (ret, output) = capture::capture(libc::STDERR_FILENO, || {
+            self.with_flags(flags, |magic| Ok(f(magic)))
+        })?;
+        Ok((ret?, warnings(&output)))
+    }
+
+    /// Returns the textual description of the `filename` along with the trace of `libmagic`
+    ///
+    /// `Flags::DEBUG` is set for the duration of the call and the lines of the evaluation
+    /// trace `libmagic` prints to `stderr` are collected, e.g. to find out which entry of a
+    /// database matched. Note that `stderr` is redirected for the whole process during the call.
+    #[cfg(unix)]
+    pub fn debug_trace<P: AsRef<Path>>(&self, filename: P) -> Result<(String, Vec<String>), FileMagicError> {
+        let flags = self.current_flags() | Flags::DEBUG;
+        let (ret, output) = capture::capture(libc::STDERR_FILENO, || {
+            self.with_flags(flags, |magic| magic.file(filename))
+        })?;
+        let trace = String::from_utf8_lossy(&output).lines().map(|line| line.to_string()).collect();
+        Ok((ret?, trace))
+    }
+
     /// Compiles the given database `filenames` for faster access
     ///
     /// The compiled files created are named from the `basename` of each file argument with '.mgc' appended to it.
     pub fn compile<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<(), FileMagicError> {
         let cookie = self.magic;
-        let db_filenames = db_filenames(filenames);
+        let db_filenames = db_filenames(filenames)?;
         let ret;
 
         unsafe {
-            ret = api::magic_compile(cookie, db_filenames);
+            ret = api::magic_compile(cookie, db_filenames_ptr(&db_filenames));
         }
         if 0 == ret { Ok(()) } else { Err(self.magic_failure()) }
     }
 
+    /// Compiles the given database `filenames` for faster access, reporting what is wrong
+    ///
+    /// This is `compile()`, but on failure the warnings `libmagic` prints to `stderr` are
+    /// appended to the returned error, see `check_verbose()`. As with `compile()`, the
+    /// compiled files are written to the current working directory rather than next to
+    /// the sources.
+    #[cfg(unix)]
+    pub fn compile_verbose<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<(), FileMagicError> {
+        let (ret, output) = capture::capture(libc::STDERR_FILENO, || self.compile(filenames))?;
+        ret.map_err(|e| with_warnings(e, &output))
+    }
+
     /// Dumps all magic entries in the given database `filenames` in a human readable format
     pub fn list<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<(), FileMagicError> {
         let cookie = self.magic;
-        let db_filenames = db_filenames(filenames);
+        let db_filenames = db_filenames(filenames)?;
         let ret;
 
         unsafe {
-            ret = api::magic_list(cookie, db_filenames);
+            ret = api::magic_list(cookie, db_filenames_ptr(&db_filenames));
         }
         if 0 == ret { Ok(()) } else { Err(self.magic_failure()) }
     }
 
+    /// Returns the flags currently in use
+    ///
+    /// Bits unknown to this crate are dropped, composite flags such as `MIME`
+    /// are contained whenever all of their parts are set.
+    pub fn get_flags(&self) -> Flags {
+        unsafe { Flags::from_bits_truncate(api::magic_getflags(self.magic)) }
+    }
+
+    /// Returns the human readable dump of all magic entries in the given database `filenames`
+    ///
+    /// This is `list()`, but with the output `libmagic` prints to `stdout` captured instead.
+    /// Note that `stdout` is redirected for the whole process during the call.
+    #[cfg(unix)]
+    pub fn list_to_string<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<String, FileMagicError> {
+        let (ret, output) = capture::capture(libc::STDOUT_FILENO, || self.list(filenames))?;
+        ret.map(|_| String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Returns the magic entries in the given database `filenames`, in listing order
+    ///
+    /// Each database is listed on its own with `list_to_string()`, so every entry knows
+    /// which of the `filenames` it comes from.
+    #[cfg(unix)]
+    pub fn list_entries<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<Vec<MagicEntry>, FileMagicError> {
+        let mut entries = Vec::new();
+        for filename in filenames {
+            let listing = self.list_to_string(&[filename])?;
+            entries.extend(MagicEntry::parse_listing(&listing, filename.as_ref()));
+        }
+        Ok(entries)
+    }
+
+    /// Returns the flags set last with `open()` or `set_flags()`
+    ///
+    /// Unlike `get_flags()` this does not ask `libmagic`, so it works with versions lacking
+    /// `magic_getflags` and it doesn't include flags `libmagic` sets on its own.
+    pub fn current_flags(&self) -> Flags {
+        self.flags.get()
+    }
+
     /// Sets the flags to use
     ///
     /// Overwrites any previously set flags, e.g. those from `load()`.
-    // TODO: libmagic itself has to magic_getflags, but we could remember them in Cookie?
     pub fn set_flags(&self, flags: Flags) -> bool {
-        unsafe { api::magic_setflags(self.magic, flags.bits()) != -1 }
+        self.try_set_flags(flags).is_ok()
+    }
+
+    /// Sets the flags to use, failing if `libmagic` rejects them
+    ///
+    /// This is `set_flags()` with an error to report, e.g. for `Flags::PRESERVE_ATIME` on
+    /// systems that can't preserve access times. The flags are left as they were then.
+    pub fn try_set_flags(&self, flags: Flags) -> Result<(), FileMagicError> {
+        if unsafe { api::magic_setflags(self.magic, flags.bits()) } == -1 {
+            // libmagic doesn't record an error of its own for this
+            let err = FileMagicError::new(format!("libmagic rejected the flags {}", flags), libc::EINVAL);
+            return Err(self.record(err));
+        }
+        self.flags.set(flags);
+        Ok(())
+    }
+
+    /// Sets the flags to use from the raw bitmask `bits`, which may include unknown bits
+    ///
+    /// An escape hatch for flags of newer `libmagic` versions which this crate doesn't
+    /// know yet, `set_flags()` should be preferred. Bits unknown to the linked `libmagic`
+    /// may be silently ignored by it.
+    ///
+    /// The bitmask can also be precomputed from known flags, e.g. in a `const` with
+    /// `Flags::MIME_TYPE.bits() | Flags::COMPRESS.bits()`.
+    pub fn set_flags_raw(&self, bits: c_int) -> bool {
+        self.set_flags(raw_flags(bits))
+    }
+
+    /// Sets `flags` in addition to the current flags
+    pub fn enable_flags(&self, flags: Flags) -> bool {
+        self.set_flags(self.current_flags() | flags)
+    }
+
+    /// Clears `flags` from the current flags, leaving the other flags as they are
+    pub fn disable_flags(&self, flags: Flags) -> bool {
+        self.set_flags(self.current_flags() - flags)
+    }
+
+    /// Sets the limit `param` to `value`
+    ///
+    /// Returns `false` if the linked `libmagic` does not support `param`.
+    pub fn set_param(&self, param: Param, value: usize) -> bool {
+        let value = value as size_t;
+        let pvalue = &value as *const size_t as *const c_void;
+        unsafe { api::magic_setparam(self.magic, param.to_raw(), pvalue) == 0 }
+    }
+
+    /// Returns the current value of the limit `param`
+    ///
+    /// Returns `None` if the linked `libmagic` does not support `param`.
+    pub fn get_param(&self, param: Param) -> Option<usize> {
+        let mut value: size_t = 0;
+        let pvalue = &mut value as *mut size_t as *mut c_void;
+        let ret = unsafe { api::magic_getparam(self.magic, param.to_raw(), pvalue) };
+        if 0 == ret { Some(value as usize) } else { None }
+    }
+
+    /// Sets the limit `param` back to its default, see `Param::default_value()`
+    pub fn reset_param(&self, param: Param) -> bool {
+        self.set_param(param, param.default_value())
+    }
+
+    /// Sets all of the `limits` at once, e.g. `Limits::untrusted()`
+    ///
+    /// Fails on the first parameter the linked `libmagic` does not support.
+    pub fn set_limits(&self, limits: &Limits) -> Result<(), FileMagicError> {
+        let params = [
+            (Param::IndirMax, limits.indir_max),
+            (Param::NameMax, limits.name_max),
+            (Param::BytesMax, limits.bytes_max),
+        ];
+        for &(param, value) in params.iter() {
+            if !self.set_param(param, value) {
+                return Err(self::FileMagicError::new(format!("unsupported parameter {:?}", param), 0));
+            }
+        }
+        Ok(())
     }
 
     /// Creates a new configuration, `flags` specify how other functions should behave
     ///
     /// This does not `load()` any databases yet.
     pub fn open(flags: Flags) -> Result<Magic, FileMagicError> {
+        let flags = flags | Flags::ERROR;
         let cookie;
         unsafe {
-            cookie = api::magic_open((flags | Flags::ERROR).bits());
+            cookie = api::magic_open(flags.bits());
         }
         if cookie.is_null() {
-            Err(self::FileMagicError {
-                desc: "errno".to_string(),
-            })
+            // There is no cookie to ask for the error, so fall back to the process errno
+            let err = io::Error::last_os_error();
+            Err(self::FileMagicError::new(
+                format!("could not open magic cookie with flags {:#x}: {}", flags.bits(), err),
+                err.raw_os_error().unwrap_or(0),
+            ))
         } else {
-            Ok(Magic { magic: cookie })
+            Ok(Magic {
+                magic: cookie,
+                flags: Cell::new(flags),
+                databases: RefCell::new(None),
+                last_error: RefCell::new(None),
+                unknown_as: RefCell::new(None),
+            })
+        }
+    }
+
+    /// Creates a new configuration like `open()` and loads the default database into it
+    ///
+    /// This is the same as calling `open()` and then `load_default()`, failing with the
+    /// error of whichever step fails.
+    pub fn open_and_load(flags: Flags) -> Result<Magic, FileMagicError> {
+        let magic = Magic::open(flags)?;
+        magic.load_default()?;
+        Ok(magic)
+    }
+
+    /// Creates a new configuration like `open()`, loads the `databases` and checks it works
+    ///
+    /// After loading, the `probe` is classified as a self-test, which must succeed with a
+    /// result `accept` agrees with, e.g. a sample of a format the `databases` know about.
+    /// No `databases` load the default database.
+    pub fn open_verified<P, F>(flags: Flags, databases: &[P], probe: &[u8], accept: F) -> Result<Magic, FileMagicError>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&str) -> bool,
+    {
+        let magic = Magic::open(flags)?;
+        magic.load(databases)?;
+        let result = magic.buffer(probe)?;
+        if !accept(&result) {
+            return Err(FileMagicError::new(format!("self-test failed: {}", result), 0));
         }
+        Ok(magic)
+    }
+
+    /// Creates a new configuration with the flags listed in the `MAGIC_FLAGS` environment variable
+    ///
+    /// The flags are separated by commas, e.g. `mime_type,compress`, see `Flags::parse_list()`.
+    /// Without the variable the configuration is opened with `Flags::NONE`, a value that is
+    /// not valid unicode fails with `EILSEQ`.
+    pub fn open_from_env() -> Result<Magic, FileMagicError> {
+        let flags = match std::env::var("MAGIC_FLAGS") {
+            Ok(list) => env_flags(Some(&list))?,
+            Err(std::env::VarError::NotPresent) => env_flags(None)?,
+            Err(std::env::VarError::NotUnicode(_)) => {
+                return Err(FileMagicError::new("MAGIC_FLAGS: not valid unicode", libc::EILSEQ));
+            }
+        };
+        Magic::open(flags)
+    }
+
+    /// Creates a new configuration from the raw bitmask `bits`, see `set_flags_raw()`
+    pub fn open_raw(bits: c_int) -> Result<Magic, FileMagicError> {
+        Magic::open(raw_flags(bits))
+    }
+
+    /// Creates an independent copy of this configuration
+    ///
+    /// The copy is opened with the same flags and loads the same databases again, it does
+    /// not share any resources with `self`.
+    pub fn try_clone(&self) -> Result<Magic, FileMagicError> {
+        let magic = Magic::open(self.flags.get())?;
+        match *self.databases.borrow() {
+            Some(Databases::Files(ref files)) => magic.load(files)?,
+            Some(Databases::Buffers(ref buffers)) => magic.load_buffers(buffers)?,
+            None => (),
+        }
+        *magic.unknown_as.borrow_mut() = self.unknown_as.borrow().clone();
+        Ok(magic)
+    }
+
+    /// Replaces the underlying `libmagic` cookie with a fresh one
+    ///
+    /// The new cookie is opened with the same flags and loads the same databases again,
+    /// which recovers from any bad internal state of the old one. If opening fails `self`
+    /// is left untouched; if loading fails the old cookie is gone nonetheless and
+    /// `is_loaded()` returns `false` until a database is loaded again.
+    pub fn reset(&mut self) -> Result<(), FileMagicError> {
+        let magic = Magic::open(self.flags.get())?;
+        let databases = self.databases.borrow_mut().take();
+        *magic.unknown_as.borrow_mut() = self.unknown_as.borrow_mut().take();
+        // Dropping the old value closes the old cookie, exactly once
+        *self = magic;
+        match databases {
+            Some(Databases::Files(ref files)) => self.load(files),
+            Some(Databases::Buffers(ref buffers)) => self.load_buffers(buffers),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the magic database and deallocates any resources used right away
+    ///
+    /// This is what dropping `self` does as well, but makes the point explicit, e.g. to
+    /// close a cookie before forking.
+    pub fn close(mut self) {
+        unsafe { api::magic_close(self.magic) }
+        // Keep the drop from closing the cookie again
+        self.magic = ptr::null();
+    }
+
+    /// Loads the default database for further queries
+    ///
+    /// This lets `libmagic` resolve the location of its compiled database, e.g. from the
+    /// `MAGIC` environment variable, and is the same as calling `load()` with no filenames.
+    ///
+    /// On Windows, where there is no standard location, a `magic.mgc` next to the
+    /// executable is preferred unless `MAGIC` is set.
+    pub fn load_default(&self) -> Result<(), FileMagicError> {
+        #[cfg(windows)]
+        {
+            if let Some(db) = beside_executable() {
+                return self.load(&[db]);
+            }
+        }
+        self.load::<&str>(&[])
     }
 
     /// Loads the given database `filenames` for further queries
     ///
-    /// Adds '.mgc'	to the database	files as appropriate.
+    /// Adds '.mgc' to the database files as appropriate. Several databases are layered
+    /// in the given order; `libmagic` skips those it cannot load and only fails if none
+    /// of them could be loaded. The error then starts with the databases passed.
     pub fn load<P: AsRef<Path>>(&self, magic_databases: &[P]) -> Result<(), FileMagicError> {
         let cookie = self.magic;
-        let db_filenames = db_filenames(magic_databases);
+        let db_filenames = db_filenames(magic_databases)?;
         let ret;
 
         unsafe {
-            ret = api::magic_load(cookie, db_filenames);
+            ret = api::magic_load(cookie, db_filenames_ptr(&db_filenames));
         }
         if 0 == ret {
+            let files = magic_databases.iter().map(|db| db.as_ref().to_path_buf()).collect();
+            *self.databases.borrow_mut() = Some(Databases::Files(files));
             Ok(())
         } else {
+            // libmagic drops the previously loaded databases as well
+            *self.databases.borrow_mut() = None;
+            let mut err = self.magic_failure();
+            let databases = match db_filenames {
+                Some(ref f) => f.to_string_lossy().into_owned(),
+                None => "default database".to_string(),
+            };
+            err.desc = format!("{}: {}", databases, err.desc);
+            Err(self.record(err))
+        }
+    }
+
+    /// Loads the given database `filenames` like `load()`, reporting whether any were skipped
+    ///
+    /// `libmagic` skips databases with invalid entries as long as at least one loads,
+    /// printing warnings to `stderr` only. These are captured to tell a `LoadStatus::Partial`
+    /// from a `LoadStatus::Full` load. Note that `stderr` is redirected for the whole
+    /// process during the call.
+    #[cfg(unix)]
+    pub fn load_with_status<P: AsRef<Path>>(&self, filenames: &[P]) -> Result<LoadStatus, FileMagicError> {
+        let (ret, output) = capture::capture(libc::STDERR_FILENO, || self.load(filenames))?;
+        let warnings = warnings(&output);
+        Ok(match ret {
+            Err(e) => LoadStatus::Failed(with_warnings(e, &output)),
+            Ok(()) if warnings.is_empty() => LoadStatus::Full,
+            Ok(()) => LoadStatus::Partial(warnings),
+        })
+    }
+
+    /// Loads the first of the database `candidates` that loads successfully
+    ///
+    /// Returns which candidate was loaded. If none of them load, the error lists why each
+    /// one failed and carries the `errno` of the last failure.
+    pub fn load_first_available<P: AsRef<Path>>(&self, candidates: &[P]) -> Result<PathBuf, FileMagicError> {
+        let mut failures = Vec::new();
+        let mut errno = 0;
+        for candidate in candidates {
+            match self.load(&[candidate]) {
+                Ok(()) => return Ok(candidate.as_ref().to_path_buf()),
+                Err(e) => {
+                    errno = e.errno;
+                    failures.push(e.desc);
+                }
+            }
+        }
+        if failures.is_empty() {
+            failures.push("no candidates given".to_string());
+        }
+        Err(FileMagicError::new(
+            format!("no database could be loaded: {}", failures.join("; ")),
+            errno,
+        ))
+    }
+
+    /// Loads the given compiled databases from memory for further queries
+    ///
+    /// The `buffers` hold the contents of compiled '.mgc' files, e.g. embedded with
+    /// `include_bytes!`. They are copied, since `libmagic` keeps using them for as long as
+    /// they are loaded.
+    pub fn load_buffers<B: AsRef<[u8]>>(&self, buffers: &[B]) -> Result<(), FileMagicError> {
+        let buffers = buffers.iter().map(|b| b.as_ref().to_vec()).collect::<Vec<_>>();
+        let mut pointers = buffers.iter().map(|b| b.as_ptr()).collect::<Vec<_>>();
+        let mut sizes = buffers.iter().map(|b| b.len() as size_t).collect::<Vec<_>>();
+        let ret;
+
+        unsafe {
+            ret = api::magic_load_buffers(
+                self.magic,
+                pointers.as_mut_ptr(),
+                sizes.as_mut_ptr(),
+                buffers.len() as size_t,
+            );
+        }
+        if 0 == ret {
+            // Moving the buffers doesn't move their contents, which libmagic points to
+            *self.databases.borrow_mut() = Some(Databases::Buffers(buffers));
+            Ok(())
+        } else {
+            *self.databases.borrow_mut() = None;
             Err(self.magic_failure())
         }
     }
 }
+
+/// Returns the `magic.mgc` next to the running executable, unless `MAGIC` is set
+#[cfg(windows)]
+fn beside_executable() -> Option<PathBuf> {
+    if std::env::var_os("MAGIC").is_some() {
+        return None;
+    }
+    let db = std::env::current_exe().ok()?.with_file_name("magic.mgc");
+    if db.is_file() {
+        Some(db)
+    } else {
+        None
+    }
+}
+
+/// Returns the MIME type of the contents of the `filename`, using the default database
+///
+/// This opens and loads a new `Magic` for every call, reuse a `Magic` to classify many files.
+pub fn detect_mime<P: AsRef<Path>>(filename: P) -> Result<String, FileMagicError> {
+    let magic = Magic::open(Flags::MIME_TYPE)?;
+    magic.load_default()?;
+    magic.file(filename)
+}
+
+/// Returns a textual description of the contents of the `filename`, using the default database
+///
+/// This opens and loads a new `Magic` for every call, reuse a `Magic` to classify many files.
+pub fn describe<P: AsRef<Path>>(filename: P) -> Result<String, FileMagicError> {
+    let magic = Magic::open(Flags::NONE)?;
+    magic.load_default()?;
+    magic.file(filename)
+}
+
+/// Returns the location of the default database, as `load_default()` would use it
+///
+/// This honours the `MAGIC` environment variable and may be a `PATH_SEPARATOR` separated
+/// list of paths. Whether the database actually exists there is not checked.
+pub fn default_path() -> Option<String> {
+    unsafe {
+        let str = api::magic_getpath(ptr::null(), 0);
+        if str.is_null() {
+            None
+        } else {
+            result_bytes(str).ok().map(|path| String::from_utf8_lossy(path).into_owned())
+        }
+    }
+}
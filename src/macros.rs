@@ -14,11 +14,11 @@
 macro_rules! magic {
     () => {
         $crate::Magic::open(Default::default())
-            .and_then(|magic| magic.load::<&str>(&[]).and_then(|_| Ok(magic)))
+            .and_then(|magic| magic.load_default().and_then(|_| Ok(magic)))
     };
     ($flags:expr) => {
         $crate::Magic::open($flags)
-            .and_then(|magic| magic.load::<&str>(&[]).and_then(|_| Ok(magic)))
+            .and_then(|magic| magic.load_default().and_then(|_| Ok(magic)))
     };
     (,$magic_databases:expr) => {
         $crate::Magic::open(Default::default())
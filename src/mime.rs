@@ -0,0 +1,145 @@
+/// Structured MIME results
+use std::fmt;
+
+/// A MIME type as reported by `libmagic`, e.g. `image/png; charset=binary`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mime {
+    /// The top-level type, e.g. `image`
+    pub type_: String,
+    /// The subtype, e.g. `png`, empty if there is none
+    pub subtype: String,
+    /// The charset, e.g. `binary`, if there is one
+    pub charset: Option<String>,
+}
+
+impl Mime {
+    /// Parses a result of `libmagic` with `Flags::MIME_TYPE` and/or `Flags::MIME_ENCODING` set
+    pub(crate) fn parse(s: &str) -> Mime {
+        let mut parts = s.splitn(2, ';');
+        let essence = parts.next().unwrap_or("").trim();
+        let charset = parts.next().map(|params| {
+            let params = params.trim();
+            params.strip_prefix("charset=").unwrap_or(params).to_string()
+        });
+
+        let mut essence = essence.splitn(2, '/');
+        Mime {
+            type_: essence.next().unwrap_or("").to_string(),
+            subtype: essence.next().unwrap_or("").to_string(),
+            charset,
+        }
+    }
+}
+
+/// Returns whether `mime_type`, e.g. `image/png`, matches the `pattern`
+///
+/// The `pattern` is either a MIME type or has a `*` wildcard as subtype, e.g. `image/*`,
+/// or as both type and subtype. MIME types are compared case-insensitively.
+pub(crate) fn matches(mime_type: &str, pattern: &str) -> bool {
+    if pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(type_) => mime_type.split('/').next().unwrap_or("").eq_ignore_ascii_case(type_),
+        None => mime_type.eq_ignore_ascii_case(pattern),
+    }
+}
+
+impl fmt::Display for Mime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.type_)?;
+        if !self.subtype.is_empty() {
+            write!(f, "/{}", self.subtype)?;
+        }
+        if let Some(ref charset) = self.charset {
+            write!(f, "; charset={}", charset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Frequent MIME types, e.g. to `match` on the result of `Magic::common_mime`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CommonMime {
+    /// `image/png`
+    Png,
+    /// `image/jpeg`
+    Jpeg,
+    /// `application/pdf`
+    Pdf,
+    /// `application/zip`
+    Zip,
+    /// `application/gzip`
+    Gzip,
+    /// `text/plain`
+    PlainText,
+    /// `application/json`
+    Json,
+    /// `application/octet-stream`
+    OctetStream,
+    /// Any other MIME type, as reported by `libmagic`
+    Other(String),
+}
+
+impl<'a> From<&'a str> for CommonMime {
+    /// Maps a MIME type such as `image/png` to its variant, falling back to `Other`
+    fn from(mime_type: &'a str) -> CommonMime {
+        match mime_type {
+            "image/png" => CommonMime::Png,
+            "image/jpeg" => CommonMime::Jpeg,
+            "application/pdf" => CommonMime::Pdf,
+            "application/zip" => CommonMime::Zip,
+            "application/gzip" | "application/x-gzip" => CommonMime::Gzip,
+            "text/plain" => CommonMime::PlainText,
+            "application/json" => CommonMime::Json,
+            "application/octet-stream" => CommonMime::OctetStream,
+            other => CommonMime::Other(other.to_string()),
+        }
+    }
+}
+
+/// Character encodings as reported by `libmagic`, see `Magic::encoding`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// `binary`, i.e. not text
+    Binary,
+    /// `us-ascii`
+    UsAscii,
+    /// `utf-8`
+    Utf8,
+    /// `utf-16le`, e.g. detected by its byte order mark
+    Utf16Le,
+    /// `utf-16be`, e.g. detected by its byte order mark
+    Utf16Be,
+    /// `utf-32le`
+    Utf32Le,
+    /// `utf-32be`
+    Utf32Be,
+    /// `iso-8859-1`
+    Iso8859_1,
+    /// `unknown-8bit`, text in an unknown 8 bit encoding
+    Unknown8Bit,
+    /// `ebcdic`
+    Ebcdic,
+    /// Any other encoding, as reported by `libmagic`
+    Other(String),
+}
+
+impl<'a> From<&'a str> for Encoding {
+    /// Maps a charset such as `utf-8` to its variant, falling back to `Other`
+    fn from(charset: &'a str) -> Encoding {
+        match charset {
+            "binary" => Encoding::Binary,
+            "us-ascii" => Encoding::UsAscii,
+            "utf-8" => Encoding::Utf8,
+            "utf-16le" => Encoding::Utf16Le,
+            "utf-16be" => Encoding::Utf16Be,
+            "utf-32le" => Encoding::Utf32Le,
+            "utf-32be" => Encoding::Utf32Be,
+            "iso-8859-1" => Encoding::Iso8859_1,
+            "unknown-8bit" => Encoding::Unknown8Bit,
+            "ebcdic" => Encoding::Ebcdic,
+            other => Encoding::Other(other.to_string()),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/// Parameters which limit how much work `libmagic` does
+use libc::c_int;
+
+/// Tunable limits of `libmagic`, see `Magic::set_param`
+///
+/// NOTE: The descriptions are taken from `man libmagic 3`. Parameters unknown to the
+/// linked `libmagic` are rejected by it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Param {
+    /// Recursion limit for indirect magic
+    IndirMax,
+    /// Use count limit for name/use magic
+    NameMax,
+    /// Max ELF program sections processed
+    ElfPhnumMax,
+    /// Max ELF sections processed
+    ElfShnumMax,
+    /// Max ELF notes processed
+    ElfNotesMax,
+    /// Length limit for regex searches
+    RegexMax,
+    /// Max number of bytes to read from file
+    BytesMax,
+    /// Max number of bytes to scan for encoding
+    EncodingMax,
+}
+
+impl Param {
+    /// Returns the `MAGIC_PARAM_*` value `libmagic` knows this parameter by
+    pub fn to_raw(self) -> c_int {
+        match self {
+            Param::IndirMax => 0,
+            Param::NameMax => 1,
+            Param::ElfPhnumMax => 2,
+            Param::ElfShnumMax => 3,
+            Param::ElfNotesMax => 4,
+            Param::RegexMax => 5,
+            Param::BytesMax => 6,
+            Param::EncodingMax => 7,
+        }
+    }
+
+    /// Returns the default value of this parameter in `libmagic` 5.44
+    pub fn default_value(self) -> usize {
+        match self {
+            Param::IndirMax => 50,
+            Param::NameMax => 50,
+            Param::ElfPhnumMax => 2048,
+            Param::ElfShnumMax => 32768,
+            Param::ElfNotesMax => 256,
+            Param::RegexMax => 8192,
+            Param::BytesMax => 7 * 1024 * 1024,
+            Param::EncodingMax => 65536,
+        }
+    }
+}
+
+/// Limits for `libmagic`'s effort, to be set at once with `Magic::set_limits`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// See `Param::IndirMax`
+    pub indir_max: usize,
+    /// See `Param::NameMax`
+    pub name_max: usize,
+    /// See `Param::BytesMax`
+    pub bytes_max: usize,
+}
+
+impl Default for Limits {
+    /// The defaults of `libmagic` 5.44
+    fn default() -> Limits {
+        Limits {
+            indir_max: Param::IndirMax.default_value(),
+            name_max: Param::NameMax.default_value(),
+            bytes_max: Param::BytesMax.default_value(),
+        }
+    }
+}
+
+impl Limits {
+    /// A preset for classifying untrusted input
+    ///
+    /// Indirect magic is followed at most 15 levels deep, name/use magic at most 30 times
+    /// and only the first 1 MiB of a file is read. This is plenty for the files `libmagic`
+    /// knows, while bounding the work deeply nested or oversized inputs can cause.
+    pub fn untrusted() -> Limits {
+        Limits {
+            indir_max: 15,
+            name_max: 30,
+            bytes_max: 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/// Readers classifying what passes through them
+use std::io::{self, Read};
+
+use {FileMagicError, Magic};
+
+/// Reader passing through everything read from `R`, classifying its beginning on the way
+///
+/// See `Magic::classifying_reader`. The prefix is read from `R` in one go on the first
+/// read and then handed out again, so consumers see the whole stream.
+pub struct ClassifyingReader<'a, R> {
+    magic: &'a Magic,
+    inner: R,
+    max_bytes: usize,
+    /// The prefix read so far, classified once it is complete
+    prefix: Vec<u8>,
+    /// How much of the `prefix` was handed out already
+    pos: usize,
+    classified: Option<Result<String, FileMagicError>>,
+}
+
+impl<'a, R: Read> ClassifyingReader<'a, R> {
+    pub(crate) fn new(magic: &'a Magic, inner: R, max_bytes: usize) -> ClassifyingReader<'a, R> {
+        ClassifyingReader {
+            magic,
+            inner,
+            max_bytes,
+            prefix: Vec::new(),
+            pos: 0,
+            classified: None,
+        }
+    }
+
+    /// Returns the description of the prefix, `None` until anything was read
+    pub fn classified_as(&self) -> Option<&Result<String, FileMagicError>> {
+        self.classified.as_ref()
+    }
+
+    /// Returns the wrapped reader, dropping the part of the prefix not read yet
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<'a, R: Read> Read for ClassifyingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.classified.is_none() {
+            // Whatever was read before an error is kept, reading again continues from there
+            let missing = self.max_bytes - self.prefix.len();
+            (&mut self.inner).take(missing as u64).read_to_end(&mut self.prefix)?;
+            self.classified = Some(self.magic.buffer(&self.prefix));
+        }
+        if self.pos < self.prefix.len() {
+            let n = (&self.prefix[self.pos..]).read(buf)?;
+            self.pos += n;
+            return Ok(n);
+        }
+        self.inner.read(buf)
+    }
+}
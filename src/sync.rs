@@ -0,0 +1,128 @@
+/// Sharing of a `Magic` between threads
+use std::{
+    ops::Deref,
+    path::Path,
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+use {FileMagicError, Flags, Magic};
+
+/// A `Magic` which can be shared between threads, e.g. in an `Arc`
+///
+/// `libmagic` cookies must not be used concurrently, so every call locks a mutex and
+/// calls are serialized: only one thread classifies at any time. If throughput matters
+/// more than sharing one loaded database, use one `Magic` per thread instead.
+pub struct SyncMagic {
+    magic: Mutex<Magic>,
+}
+
+impl SyncMagic {
+    /// Wraps `magic` for sharing between threads
+    pub fn new(magic: Magic) -> SyncMagic {
+        SyncMagic {
+            magic: Mutex::new(magic),
+        }
+    }
+
+    /// Locks the wrapped `Magic` for exclusive use, e.g. to call several methods in a row
+    pub fn lock(&self) -> MutexGuard<'_, Magic> {
+        // A panic while locked can't leave the cookie itself in an inconsistent state
+        self.magic.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns a textual description of the contents of the `filename`
+    pub fn file<P: AsRef<Path>>(&self, filename: P) -> Result<String, FileMagicError> {
+        self.lock().file(filename)
+    }
+
+    /// Returns a textual description of the contents of the `buffer`
+    pub fn buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<String, FileMagicError> {
+        self.lock().buffer(buffer)
+    }
+
+    /// Returns the wrapped `Magic`
+    pub fn into_inner(self) -> Magic {
+        self.magic.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A fixed number of loaded `Magic`s which can be shared between threads, e.g. in an `Arc`
+///
+/// Unlike `SyncMagic`, up to `size` threads classify at the same time. Further threads
+/// block until one of the `Magic`s is returned to the pool.
+pub struct MagicPool {
+    idle: Mutex<Vec<Magic>>,
+    returned: Condvar,
+}
+
+impl MagicPool {
+    /// Opens `size` configurations with `flags` and loads the `databases` into each
+    ///
+    /// No `databases` load the default database, like `Magic::load_default()`.
+    pub fn new<P: AsRef<Path>>(size: usize, flags: Flags, databases: &[P]) -> Result<MagicPool, FileMagicError> {
+        if size == 0 {
+            return Err(FileMagicError::new("a pool needs at least one cookie", libc::EINVAL));
+        }
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            let magic = Magic::open(flags)?;
+            magic.load(databases)?;
+            idle.push(magic);
+        }
+        Ok(MagicPool {
+            idle: Mutex::new(idle),
+            returned: Condvar::new(),
+        })
+    }
+
+    /// Checks out a `Magic` for exclusive use, blocking until one is available
+    ///
+    /// It is returned to the pool when the `PooledMagic` is dropped.
+    pub fn get(&self) -> PooledMagic<'_> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(magic) = idle.pop() {
+                return PooledMagic {
+                    pool: self,
+                    magic: Some(magic),
+                };
+            }
+            idle = self.returned.wait(idle).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Returns a textual description of the contents of the `filename`
+    pub fn file<P: AsRef<Path>>(&self, filename: P) -> Result<String, FileMagicError> {
+        self.get().file(filename)
+    }
+
+    /// Returns a textual description of the contents of the `buffer`
+    pub fn buffer<B: AsRef<[u8]>>(&self, buffer: B) -> Result<String, FileMagicError> {
+        self.get().buffer(buffer)
+    }
+}
+
+/// A `Magic` checked out of a `MagicPool`, see `MagicPool::get`
+pub struct PooledMagic<'a> {
+    pool: &'a MagicPool,
+    /// Only `None` while being returned
+    magic: Option<Magic>,
+}
+
+impl<'a> Deref for PooledMagic<'a> {
+    type Target = Magic;
+
+    fn deref(&self) -> &Magic {
+        self.magic.as_ref().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledMagic<'a> {
+    /// Returns the `Magic` to the pool and wakes up a thread waiting for one
+    fn drop(&mut self) {
+        if let Some(magic) = self.magic.take() {
+            self.pool.idle.lock().unwrap_or_else(|e| e.into_inner()).push(magic);
+            self.pool.returned.notify_one();
+        }
+    }
+}
@@ -0,0 +1,135 @@
+/// A minimal reader of tar archives, just enough to classify their members
+use std::io::{self, Read, Seek, SeekFrom};
+
+const BLOCK: u64 = 512;
+
+/// What a member of a tar archive is
+pub enum Kind {
+    File,
+    Directory,
+    Symlink(String),
+    HardLink(String),
+    Other,
+}
+
+/// A member of a tar archive, with at most the requested prefix of its contents
+pub struct Member {
+    pub name: String,
+    pub kind: Kind,
+    pub contents: Vec<u8>,
+}
+
+/// Returns the NUL-terminated string in `field`
+fn string(field: &[u8]) -> String {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// Parses the octal number in `field`, which may be padded with spaces and NULs
+fn octal(field: &[u8]) -> io::Result<u64> {
+    let digits = string(field);
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid size in tar header"))
+}
+
+/// Returns the `key=value` records of a pax extended header
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    // Each record is `<length> <key>=<value>\n`, the length counting the whole record
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let len = std::str::from_utf8(&rest[..space]).ok().and_then(|len| len.parse::<usize>().ok());
+        let len = match len {
+            Some(len) if len > space && len <= rest.len() => len,
+            _ => break,
+        };
+        let record = &rest[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(eq) = record.iter().position(|&b| b == b'=') {
+            let key = String::from_utf8_lossy(&record[..eq]).into_owned();
+            let value = String::from_utf8_lossy(&record[eq + 1..]).into_owned();
+            records.push((key, value));
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+/// Reads the next member of the archive `tar`, none at its end
+///
+/// Only the first `max_bytes` of the contents are read, the rest is skipped. GNU long
+/// names and link targets are supported, as are the `path` and `linkpath` of pax
+/// extended headers. Global pax headers are skipped.
+pub fn next_member<R: Read + Seek>(tar: &mut R, max_bytes: u64) -> io::Result<Option<Member>> {
+    let mut long_name = None;
+    let mut long_link = None;
+    loop {
+        let mut header = [0u8; BLOCK as usize];
+        match tar.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        // The archive ends with blocks of zeros
+        if header.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+
+        let size = octal(&header[124..136])?;
+        // Contents are padded to whole blocks
+        let padded = (size + BLOCK - 1) & !(BLOCK - 1);
+        let flag = header[156];
+        // What describes the next member is read in full, regardless of `max_bytes`
+        let wanted = match flag {
+            b'L' | b'K' | b'x' | b'g' => size,
+            _ => std::cmp::min(size, max_bytes),
+        };
+        let mut contents = Vec::new();
+        tar.by_ref().take(wanted).read_to_end(&mut contents)?;
+        tar.seek(SeekFrom::Current((padded - contents.len() as u64) as i64))?;
+
+        match flag {
+            b'L' => {
+                long_name = Some(string(&contents));
+                continue;
+            }
+            b'K' => {
+                long_link = Some(string(&contents));
+                continue;
+            }
+            b'x' => {
+                for (key, value) in pax_records(&contents) {
+                    match key.as_str() {
+                        "path" => long_name = Some(value),
+                        "linkpath" => long_link = Some(value),
+                        _ => (),
+                    }
+                }
+                continue;
+            }
+            b'g' => continue,
+            _ => (),
+        }
+        let link = long_link.take().unwrap_or_else(|| string(&header[157..257]));
+        let kind = match flag {
+            b'0' | b'\0' | b'7' => Kind::File,
+            b'5' => Kind::Directory,
+            b'2' => Kind::Symlink(link),
+            b'1' => Kind::HardLink(link),
+            _ => Kind::Other,
+        };
+        let name = match long_name.take() {
+            Some(name) => name,
+            // Only POSIX ustar has a prefix, GNU tar (`ustar  \0`) keeps the times there instead
+            None if &header[257..263] == b"ustar\0" && header[345] != 0 => {
+                format!("{}/{}", string(&header[345..500]), string(&header[0..100]))
+            }
+            None => string(&header[0..100]),
+        };
+        return Ok(Some(Member { name, kind, contents }));
+    }
+}
@@ -1,9 +1,15 @@
 extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use super::{
-    Magic, Flags,
-    version as ver
+    Magic, MagicBuilder, Classification, CommonMime, Encoding, Flags, FileMagicError, Limits,
+    MagicPool, MatchSource, Mime, Param, SyncMagic,
+    version as ver, libmagic_version,
+    default_path, describe, detect_mime, PATH_SEPARATOR,
 };
+#[cfg(unix)]
+use super::{LoadStatus, MagicEntry};
 
 
 #[test]
@@ -12,6 +18,12 @@ fn version() {
     assert!(re.is_match(ver()));
 }
 
+#[test]
+fn linked_libmagic_version() {
+    // magic_version() only exists since libmagic 5.13
+    assert!(libmagic_version() >= 513);
+}
+
 #[test]
 fn load_default_db() {
     let cookie = Magic::open(Flags::NONE | Flags::ERROR).unwrap();
@@ -21,31 +33,31 @@ fn load_default_db() {
 #[test]
 fn load_one_db() {
     let cookie = Magic::open(Flags::NONE | Flags::ERROR).unwrap();
-    assert!(cookie.load(&vec!["data/db-images-png"]).is_ok());
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
 }
 
 #[test]
 fn get_file_mime() {
     let cookie = Magic::open(Flags::NONE).unwrap();
-    assert!(cookie.load(&vec!["data/db-images-png"]).is_ok());
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
 
     let path = "data/rust-logo-128x128-blk.png";
 
-    assert_eq!(cookie.file(&path).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+    assert_eq!(cookie.file(path).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
 
     cookie.set_flags(Flags::MIME_TYPE);
-    assert_eq!(cookie.file(&path).unwrap(), "image/png");
+    assert_eq!(cookie.file(path).unwrap(), "image/png");
 
     cookie.set_flags(Flags::MIME_TYPE | Flags::MIME_ENCODING);
-    assert_eq!(cookie.file(&path).unwrap(), "image/png; charset=binary");
+    assert_eq!(cookie.file(path).unwrap(), "image/png; charset=binary");
 }
 
 #[test]
 fn get_buffer_mime() {
     let cookie = Magic::open(Flags::NONE).unwrap();
-    assert!(cookie.load(&vec!["data/db-python"].as_slice()).is_ok());
+    assert!(cookie.load(&["data/db-python"]).is_ok());
 
-    let s = b"#!/usr/bin/env python\nprint('Hello, world!')";
+    let s = "#!/usr/bin/env python\nprint('Hello, world!')";
     assert_eq!(cookie.buffer(s).unwrap(), "Python script, ASCII text executable");
 
     cookie.set_flags(Flags::MIME_TYPE);
@@ -59,7 +71,9 @@ fn file_error() {
 
     let ret = cookie.file("non-existent_file.txt");
     assert!(ret.is_err());
-    assert_eq!(ret.err().unwrap().desc, "cannot stat `non-existent_file.txt' (No such file or directory)");
+    let err = ret.err().unwrap();
+    assert_eq!(err.desc, "cannot stat `non-existent_file.txt' (No such file or directory)");
+    assert_eq!(err.errno, libc::ENOENT);
 }
 
 #[test]
@@ -70,7 +84,7 @@ fn macro_load_default_db() {
 #[test]
 fn macro_load_one_db() {
     assert!(
-        magic!(,&vec!["data/db-images-png"]).is_ok()
+        magic!(,&["data/db-images-png"]).is_ok()
     );
 }
 
@@ -79,7 +93,7 @@ fn macro_load_one_db_with_flags() {
     assert!(
         magic!(
         Flags::NONE | Flags::ERROR,
-        &vec!["data/db-images-png"]
+        &["data/db-images-png"]
         ).is_ok()
     );
 }
@@ -89,19 +103,19 @@ fn macro_get_file_mime() {
     let cookie = magic!().unwrap();
     let path = "data/rust-logo-128x128-blk.png";
 
-    assert_eq!(cookie.file(&path).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+    assert_eq!(cookie.file(path).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
 
     cookie.set_flags(Flags::MIME_TYPE);
-    assert_eq!(cookie.file(&path).unwrap(), "image/png");
+    assert_eq!(cookie.file(path).unwrap(), "image/png");
 
     cookie.set_flags(Flags::MIME_TYPE | Flags::MIME_ENCODING);
-    assert_eq!(cookie.file(&path).unwrap(), "image/png; charset=binary");
+    assert_eq!(cookie.file(path).unwrap(), "image/png; charset=binary");
 }
 
 #[test]
 fn macro_get_buffer_mime() {
     let cookie = magic!().unwrap();
-    assert!(cookie.load(&vec!["data/db-python"].as_slice()).is_ok());
+    assert!(cookie.load(&["data/db-python"]).is_ok());
 
     let s = b"#!/usr/bin/env python\nprint('Hello, world!')";
     assert_eq!(cookie.buffer(s).unwrap(), "Python script, ASCII text executable");
@@ -109,3 +123,1524 @@ fn macro_get_buffer_mime() {
     cookie.set_flags(Flags::MIME_TYPE);
     assert_eq!(cookie.buffer(s).unwrap(), "text/x-python");
 }
+
+#[cfg(unix)]
+#[test]
+fn get_descriptor_mime() {
+    use std::os::unix::io::AsRawFd;
+
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
+
+    let file = std::fs::File::open("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(cookie.descriptor(file.as_raw_fd()).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+
+    // libmagic must leave the descriptor open for the caller
+    cookie.set_flags(Flags::MIME_TYPE);
+    assert_eq!(cookie.descriptor(file.as_raw_fd()).unwrap(), "image/png");
+}
+
+#[test]
+fn file_errno() {
+    let cookie = Magic::open(Flags::NONE | Flags::ERROR).unwrap();
+    assert!(cookie.load::<String>(&[]).is_ok());
+
+    assert!(cookie.file("non-existent_file.txt").is_err());
+    assert_eq!(cookie.errno(), libc::ENOENT);
+}
+
+#[test]
+fn error_propagation() {
+    fn describe(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let cookie = Magic::open(Flags::NONE)?;
+        cookie.load::<String>(&[])?;
+        Ok(cookie.file(path)?)
+    }
+
+    let err = describe("non-existent_file.txt").unwrap_err();
+    let err = err.downcast_ref::<FileMagicError>().unwrap();
+    assert_eq!(err.errno(), libc::ENOENT);
+    assert!(err.desc().starts_with("cannot stat"));
+}
+
+#[test]
+fn get_flags() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert_eq!(cookie.get_flags(), Flags::ERROR);
+
+    cookie.set_flags(Flags::MIME_TYPE | Flags::MIME_ENCODING);
+    assert_eq!(cookie.get_flags(), Flags::MIME);
+    assert!(cookie.get_flags().contains(Flags::MIME_TYPE));
+}
+
+#[test]
+fn load_default() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+}
+
+#[test]
+fn load_many_dbs() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load(&["data/db-images-png", "data/db-python"]).is_ok());
+
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+    assert_eq!(cookie.buffer(b"#!/usr/bin/env python\nprint('Hello, world!')").unwrap(), "Python script, ASCII text executable");
+}
+
+#[test]
+fn load_many_dbs_missing_one() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load(&["data/db-images-png", "data/non-existent-db"]).is_ok());
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+
+    let err = cookie.load(&["data/non-existent-db", "data/other-non-existent-db"]).unwrap_err();
+    let joined = format!("data/non-existent-db{}data/other-non-existent-db: ", PATH_SEPARATOR);
+    assert!(err.desc().starts_with(&joined));
+}
+
+#[test]
+fn set_and_get_param() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+
+    assert!(cookie.set_param(Param::BytesMax, 16 * 1024 * 1024));
+    assert_eq!(cookie.get_param(Param::BytesMax), Some(16 * 1024 * 1024));
+
+    assert!(cookie.set_param(Param::IndirMax, 5));
+    assert_eq!(cookie.get_param(Param::IndirMax), Some(5));
+}
+
+#[test]
+fn flags_compose() {
+    let flags = Flags::MIME_TYPE | Flags::MIME_ENCODING;
+    assert_eq!(flags, Flags::MIME);
+
+    let cookie = Magic::open(flags).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png; charset=binary");
+}
+
+#[test]
+fn flags_toggle() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    let mut flags = Flags::empty();
+
+    flags.insert(Flags::COMPRESS);
+    assert!(flags.contains(Flags::COMPRESS));
+    assert!(cookie.set_flags(flags));
+    assert!(cookie.get_flags().contains(Flags::COMPRESS));
+
+    flags.remove(Flags::COMPRESS);
+    assert!(flags.is_empty());
+    assert!(cookie.set_flags(flags));
+    assert!(!cookie.get_flags().contains(Flags::COMPRESS));
+}
+
+#[test]
+fn flags_display() {
+    assert_eq!(Flags::NONE.to_string(), "MAGIC_NONE");
+    assert_eq!(Flags::MIME_TYPE.to_string(), "MAGIC_MIME_TYPE");
+    assert_eq!((Flags::MIME_TYPE | Flags::MIME_ENCODING).to_string(), "MAGIC_MIME");
+    assert_eq!((Flags::ERROR | Flags::MIME).to_string(), "MAGIC_MIME | MAGIC_ERROR");
+}
+
+#[test]
+fn flags_debug() {
+    assert_eq!(format!("{:?}", Flags::NONE), "Flags(MAGIC_NONE, 0x0)");
+    assert_eq!(format!("{:?}", Flags::MIME | Flags::ERROR), "Flags(MAGIC_MIME | MAGIC_ERROR, 0x610)");
+}
+
+#[test]
+fn flags_bits() {
+    assert_eq!(Flags::all().bits(), 0x77fffff);
+    assert_eq!(Flags::from_bits(0x000410), Some(Flags::MIME));
+    assert_eq!(Flags::from_bits(0x40000004), None);
+    assert_eq!(Flags::from_bits_truncate(0x40000004), Flags::COMPRESS);
+    assert_eq!(!Flags::all(), Flags::NONE);
+    assert_eq!(Flags::MIME - Flags::MIME_TYPE, Flags::MIME_ENCODING);
+    assert_eq!([Flags::MIME_TYPE, Flags::MIME_ENCODING].iter().cloned().collect::<Flags>(), Flags::MIME);
+}
+
+#[test]
+fn flags_split_bits() {
+    assert_eq!(Flags::split_bits(0x000410), (Flags::MIME, 0));
+    assert_eq!(Flags::split_bits(0x40000004), (Flags::COMPRESS, 0x40000000));
+}
+
+#[test]
+fn flags_from_str() {
+    assert_eq!("MAGIC_COMPRESS".parse::<Flags>().unwrap(), Flags::COMPRESS);
+    assert_eq!(" mime_type ".parse::<Flags>().unwrap(), Flags::MIME_TYPE);
+    assert_eq!("Magic_Mime".parse::<Flags>().unwrap(), Flags::MIME);
+    assert_eq!("none".parse::<Flags>().unwrap(), Flags::NONE);
+
+    let err = "mime_tpye".parse::<Flags>().unwrap_err();
+    assert_eq!(err.desc(), "unknown flag `mime_tpye`");
+}
+
+#[cfg(unix)]
+#[test]
+fn list_to_string() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+
+    let listing = cookie.list_to_string(&["data/db-images-png"]).unwrap();
+    assert!(listing.contains("PNG image data"));
+}
+
+#[test]
+fn file_all_matches() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let path = "data/rust-logo-128x128-blk.png";
+    let matches = cookie.file_all(path).unwrap();
+    assert_eq!(matches[0], "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+    assert!(matches.len() > 1);
+
+    // The previous flags are restored afterwards
+    assert!(!cookie.get_flags().contains(Flags::CONTINUE));
+    assert_eq!(cookie.file(path).unwrap(), matches[0]);
+
+    cookie.set_flags(Flags::RAW);
+    assert_eq!(cookie.file_all(path).unwrap(), matches);
+}
+
+#[test]
+fn get_file_structured_mime() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mime = cookie.mime("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(mime.type_, "image");
+    assert_eq!(mime.subtype, "png");
+    assert_eq!(mime.charset, Some("binary".to_string()));
+    assert_eq!(mime.to_string(), "image/png; charset=binary");
+}
+
+#[test]
+fn parse_partial_mime() {
+    let mime = Mime::parse("image/png");
+    assert_eq!((mime.type_.as_str(), mime.subtype.as_str(), mime.charset), ("image", "png", None));
+
+    let mime = Mime::parse("binary");
+    assert_eq!((mime.type_.as_str(), mime.subtype.as_str(), mime.charset), ("binary", "", None));
+}
+
+#[test]
+fn get_reader_mime() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let file = std::fs::File::open("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(cookie.reader(file, 1024).unwrap(), "image/png");
+
+    // A stream shorter than max_bytes is classified as far as it goes
+    let bytes = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(cookie.reader(&bytes[..64], 1024 * 1024).unwrap(), "image/png");
+}
+
+#[cfg(unix)]
+#[test]
+fn file_non_utf8_path() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = std::env::temp_dir().join("filemagic-non-utf8-path");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(OsStr::from_bytes(b"logo-\xff.png"));
+    std::fs::copy("data/rust-logo-128x128-blk.png", &path).unwrap();
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert_eq!(cookie.file(&path).unwrap(), "image/png");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_nul_path() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let err = cookie.file("data/\0.png").unwrap_err();
+    assert_eq!(err.errno(), libc::EINVAL);
+}
+
+#[test]
+fn sync_magic_threads() {
+    use std::{sync::Arc, thread};
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let cookie = Arc::new(SyncMagic::new(cookie));
+
+    let workers = (0..4)
+        .map(|_| {
+            let cookie = Arc::clone(&cookie);
+            thread::spawn(move || cookie.file("data/rust-logo-128x128-blk.png").unwrap())
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "image/png");
+    }
+}
+
+#[test]
+fn try_clone() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
+
+    let clone = cookie.try_clone().unwrap();
+    drop(cookie);
+    assert!(clone.get_flags().contains(Flags::MIME_TYPE | Flags::ERROR));
+    assert_eq!(clone.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+}
+
+#[test]
+fn current_flags() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::ERROR);
+
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::ERROR);
+
+    cookie.set_flags(Flags::COMPRESS);
+    assert_eq!(cookie.current_flags(), Flags::COMPRESS);
+}
+
+#[test]
+fn one_shot_detection() {
+    let path = "data/rust-logo-128x128-blk.png";
+    assert_eq!(detect_mime(path).unwrap(), "image/png");
+    assert_eq!(describe(path).unwrap(), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced");
+
+    assert!(detect_mime("non-existent_file.txt").is_err());
+}
+
+#[test]
+fn builder() {
+    let cookie = MagicBuilder::new()
+        .flag(Flags::MIME_TYPE)
+        .database("data/db-images-png")
+        .param(Param::BytesMax, 1024 * 1024)
+        .open()
+        .unwrap();
+
+    assert_eq!(cookie.get_param(Param::BytesMax), Some(1024 * 1024));
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+}
+
+#[test]
+fn builder_load_error() {
+    assert!(MagicBuilder::new().database("data/non-existent-db").open().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn check_verbose() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.check_verbose(&["data/db-images-png"]).is_ok());
+
+    let err = cookie.check_verbose(&["data/db-broken"]).unwrap_err();
+    assert!(err.desc().contains("data/db-broken, 3: Warning: type `bogustype x foo' invalid"));
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_verbose() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+
+    let err = cookie.compile_verbose(&["data/db-broken"]).unwrap_err();
+    assert!(err.desc().contains("data/db-broken, 2: Warning: Unparsable number"));
+}
+
+#[test]
+fn identify_owned_bytes() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let data = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(cookie.identify_bytes(data).unwrap(), "image/png");
+    assert_eq!(cookie.identify_bytes(Vec::new()).unwrap(), cookie.buffer(b"").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn walk_dir() {
+    let dir = std::env::temp_dir().join("filemagic-walk-dir");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::copy("data/rust-logo-128x128-blk.png", dir.join("logo.png")).unwrap();
+    std::fs::copy("data/rust-logo-128x128-blk.png", dir.join("sub/logo.png")).unwrap();
+    // A symlink loop must not be followed
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mut entries = cookie.walk_dir(&dir, false).map(|(p, r)| (p, r.unwrap())).collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(entries, vec![
+        (dir.join("logo.png"), "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced".to_string()),
+        (dir.join("sub"), "directory".to_string()),
+    ]);
+
+    let mut entries = cookie.walk_dir(&dir, true).map(|(p, r)| (p, r.unwrap())).collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[3].0, dir.join("sub/loop"));
+    assert!(entries[3].1.starts_with("symbolic link to"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn apple_codes() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let codes = cookie.apple("data/rust-logo-128x128-blk.png").unwrap().unwrap();
+    assert_eq!(codes.0.len(), 4);
+    assert_eq!(codes.1.len(), 4);
+    assert!(!cookie.current_flags().contains(Flags::APPLE));
+}
+
+#[test]
+fn classify() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.classify("data/rust-logo-128x128-blk.png").unwrap(), Classification {
+        description: "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced".to_string(),
+        mime_type: "image/png".to_string(),
+        encoding: "binary".to_string(),
+    });
+}
+
+#[test]
+fn extensions() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.extensions("data/rust-logo-128x128-blk.png").unwrap(), vec!["png"]);
+    assert!(cookie.extensions("data/db-python").unwrap().is_empty());
+}
+
+#[test]
+fn no_check_json() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let json = b"{\"key\": [1, 2, 3]}";
+    assert_eq!(cookie.buffer(json).unwrap(), "application/json");
+
+    cookie.set_flags(Flags::MIME_TYPE | Flags::NO_CHECK_JSON);
+    assert_eq!(cookie.buffer(json).unwrap(), "text/plain");
+}
+
+#[test]
+fn flags_validate() {
+    assert!(Flags::NONE.validate().is_ok());
+    assert!((Flags::MIME | Flags::COMPRESS | Flags::COMPRESS_TRANSP).validate().is_ok());
+
+    let err = (Flags::COMPRESS | Flags::NO_CHECK_COMPRESS).validate().unwrap_err();
+    assert_eq!(err.desc(), "MAGIC_COMPRESS contradicts MAGIC_NO_CHECK_COMPRESS");
+
+    let err = (Flags::MIME_TYPE | Flags::EXTENSION | Flags::COMPRESS_TRANSP).validate().unwrap_err();
+    assert_eq!(
+        err.desc(),
+        "MAGIC_COMPRESS_TRANSP has no effect without MAGIC_COMPRESS; \
+         only one of MAGIC_MIME, MAGIC_APPLE and MAGIC_EXTENSION can be reported"
+    );
+}
+
+#[test]
+fn file_before_load() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(!cookie.is_loaded());
+
+    let err = cookie.file("data/rust-logo-128x128-blk.png").unwrap_err();
+    assert_eq!(err.desc(), "no magic database loaded");
+    assert!(cookie.buffer(b"").is_err());
+
+    assert!(cookie.load(&["data/non-existent-db"]).is_err());
+    assert!(!cookie.is_loaded());
+    assert!(cookie.load_default().is_ok());
+    assert!(cookie.is_loaded());
+    assert!(cookie.load(&["data/non-existent-db"]).is_err());
+    assert!(!cookie.is_loaded());
+}
+
+#[test]
+fn load_buffers() {
+    // The compiled default database, wherever the distribution installs it
+    let database = default_path()
+        .unwrap()
+        .split(PATH_SEPARATOR)
+        .find_map(|path| std::fs::read(format!("{}.mgc", path)).ok())
+        .unwrap();
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_buffers(&[database]).is_ok());
+    assert!(cookie.is_loaded());
+
+    let clone = cookie.try_clone().unwrap();
+    drop(cookie);
+    assert_eq!(clone.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    assert!(clone.load_buffers(&[b"not a compiled database"]).is_err());
+    assert!(!clone.is_loaded());
+}
+
+#[cfg(unix)]
+#[test]
+fn get_fd_mime() {
+    use std::io::Read;
+
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mut file = std::fs::File::open("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(cookie.fd_mime(&file).unwrap().to_string(), "image/png; charset=binary");
+
+    // The file is still open and at its start
+    let mut header = [0; 4];
+    file.read_exact(&mut header).unwrap();
+    assert_eq!(&header, b"\x89PNG");
+}
+
+#[test]
+fn get_buffer_at_mime() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let png = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    let mut data = vec![0; 512];
+    data.extend_from_slice(&png);
+
+    assert_eq!(cookie.buffer_at(&data, 512, png.len()).unwrap(), "image/png");
+    assert!(cookie.buffer_at(&data, 512, png.len() + 1).is_err());
+    assert!(cookie.buffer_at(&data, usize::MAX, 2).is_err());
+}
+
+#[test]
+fn get_empty_buffer_mime() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.buffer(b"").unwrap(), "empty");
+    assert!(cookie.buffer([0]).is_ok());
+    assert!(cookie.buffer(b"a").is_ok());
+
+    cookie.set_flags(Flags::MIME_TYPE);
+    assert_eq!(cookie.buffer(b"").unwrap(), "application/x-empty");
+}
+
+#[test]
+fn set_limits() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert_eq!(cookie.get_param(Param::BytesMax), Some(Limits::default().bytes_max));
+
+    assert!(cookie.set_limits(&Limits::untrusted()).is_ok());
+    assert_eq!(cookie.get_param(Param::IndirMax), Some(15));
+    assert_eq!(cookie.get_param(Param::NameMax), Some(30));
+    assert_eq!(cookie.get_param(Param::BytesMax), Some(1024 * 1024));
+}
+
+#[test]
+fn was_decompressed() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert!(cookie.was_decompressed("data/rust-logo-128x128-blk.png.gz").unwrap());
+    assert!(!cookie.was_decompressed("data/rust-logo-128x128-blk.png").unwrap());
+}
+
+#[test]
+fn enable_disable_flags() {
+    let cookie = Magic::open(Flags::COMPRESS).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert!(cookie.enable_flags(Flags::MIME_TYPE));
+    assert_eq!(cookie.current_flags(), Flags::COMPRESS | Flags::MIME_TYPE | Flags::ERROR);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    assert!(cookie.disable_flags(Flags::MIME_TYPE));
+    assert_eq!(cookie.current_flags(), Flags::COMPRESS | Flags::ERROR);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn flags_serde() {
+    let flags = Flags::MIME | Flags::COMPRESS;
+    let json = serde_json::to_string(&flags).unwrap();
+    assert_eq!(json, r#"["MAGIC_MIME","MAGIC_COMPRESS"]"#);
+    assert_eq!(serde_json::from_str::<Flags>(&json).unwrap(), flags);
+    assert_eq!(serde_json::from_str::<Flags>(r#"["mime_type", "MAGIC_MIME_ENCODING"]"#).unwrap(), Flags::MIME);
+
+    let err = serde_json::from_str::<Flags>(r#"["MAGIC_COMPRES"]"#).unwrap_err();
+    assert!(err.to_string().contains("unknown flag `MAGIC_COMPRES`"));
+}
+
+#[test]
+fn files_in_order() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let paths = ["data/rust-logo-128x128-blk.png", "data/nonexistent", "data/db-broken"];
+    let mut files = cookie.files(&paths);
+    let (path, result) = files.next().unwrap();
+    assert_eq!(*path, "data/rust-logo-128x128-blk.png");
+    assert_eq!(result.unwrap(), "image/png");
+    let (path, result) = files.next().unwrap();
+    assert_eq!(*path, "data/nonexistent");
+    assert_eq!(result.unwrap_err().errno(), libc::ENOENT);
+    assert_eq!(cookie.errno(), libc::ENOENT);
+    assert_eq!(files.size_hint(), (1, Some(1)));
+}
+
+#[test]
+fn file_bytes_not_utf8() {
+    let cookie = Magic::open(Flags::RAW).unwrap();
+    assert!(cookie.load(&["data/db-latin1"]).is_ok());
+
+    assert_eq!(cookie.file_bytes("data/latin1.txt").unwrap(), b"caf\xe9 file".to_vec());
+    assert_eq!(cookie.file("data/latin1.txt").unwrap_err().errno(), libc::EILSEQ);
+}
+
+#[test]
+fn is_text() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert!(cookie.is_text("data/latin1.txt").unwrap());
+    assert!(!cookie.is_text("data/rust-logo-128x128-blk.png").unwrap());
+    assert_eq!(cookie.is_text("data/nonexistent").unwrap_err().errno(), libc::ENOENT);
+    assert_eq!(cookie.current_flags(), Flags::ERROR);
+}
+
+#[test]
+fn open_and_load() {
+    let cookie = Magic::open_and_load(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.is_loaded());
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+}
+
+#[test]
+fn inner_and_container_type() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.inner_type("data/rust-logo-128x128-blk.png.gz").unwrap(), "image/png");
+    assert_eq!(cookie.container_type("data/rust-logo-128x128-blk.png.gz").unwrap(), "application/gzip");
+    assert_eq!(cookie.inner_type("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::ERROR);
+
+    assert!(cookie.set_flags(Flags::NONE));
+    let inner = cookie.inner_type("data/rust-logo-128x128-blk.png.gz").unwrap();
+    assert!(inner.starts_with("PNG image data"));
+    assert!(!inner.contains("gzip"));
+}
+
+#[test]
+fn default_database_path() {
+    let path = default_path().unwrap();
+    assert!(!path.is_empty());
+    assert!(path.split(PATH_SEPARATOR).all(|p| p.contains("magic")));
+}
+
+#[test]
+fn reset() {
+    let db = std::env::temp_dir().join("filemagic-reset-db");
+    std::fs::copy("data/db-images-png", &db).unwrap();
+
+    let mut cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.reset().is_ok());
+    assert!(!cookie.is_loaded());
+
+    assert!(cookie.load(&[&db]).is_ok());
+    assert!(cookie.reset().is_ok());
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::ERROR);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    std::fs::remove_file(&db).unwrap();
+    assert!(cookie.reset().is_err());
+    assert!(!cookie.is_loaded());
+    assert!(cookie.file("data/rust-logo-128x128-blk.png").is_err());
+}
+
+#[test]
+fn load_first_available() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+
+    let loaded = cookie.load_first_available(&["data/nonexistent", "data/db-images-png", "data/db-python"]);
+    assert_eq!(loaded.unwrap(), std::path::PathBuf::from("data/db-images-png"));
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    let err = cookie.load_first_available(&["data/nonexistent", "data/nonexistent2"]).unwrap_err();
+    assert!(err.desc().starts_with("no database could be loaded: data/nonexistent: "));
+    assert!(err.desc().contains("; data/nonexistent2: "));
+    assert!(!cookie.is_loaded());
+
+    assert!(cookie.load_first_available::<&str>(&[]).is_err());
+}
+
+#[test]
+fn file_into_reuses_buffer() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mut out = b"previous contents".to_vec();
+    assert!(cookie.file_into("data/rust-logo-128x128-blk.png", &mut out).is_ok());
+    assert_eq!(out, b"image/png");
+    assert!(cookie.file_into("data/nonexistent", &mut out).is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn magic_pool_threads() {
+    use std::{sync::Arc, thread};
+
+    assert_eq!(MagicPool::new::<&str>(0, Flags::NONE, &[]).err().unwrap().errno(), libc::EINVAL);
+    assert!(MagicPool::new(2, Flags::NONE, &["data/nonexistent"]).is_err());
+
+    let pool = Arc::new(MagicPool::new(2, Flags::MIME_TYPE, &["data/db-images-png"]).unwrap());
+    let png = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    let workers = (0..8)
+        .map(|_| {
+            let pool = Arc::clone(&pool);
+            let png = png.clone();
+            thread::spawn(move || pool.buffer(&png).unwrap())
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "image/png");
+    }
+
+    let first = pool.get();
+    let second = pool.get();
+    assert!(first.is_loaded() && second.is_loaded());
+}
+
+#[cfg(unix)]
+#[test]
+fn mmap_file_header() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.mmap_file("data/rust-logo-128x128-blk.png", 64).unwrap(), "image/png");
+    assert_eq!(cookie.mmap_file("data/rust-logo-128x128-blk.png", 1 << 30).unwrap(), "image/png");
+    assert_eq!(cookie.mmap_file("data/rust-logo-128x128-blk.png", 0).unwrap(), "application/x-empty");
+    assert_eq!(cookie.mmap_file("data/nonexistent", 64).unwrap_err().errno(), libc::ENOENT);
+}
+
+#[test]
+fn preferred_extension() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let jpeg = std::env::temp_dir().join("filemagic-preferred-extension");
+    std::fs::write(&jpeg, b"\xff\xd8\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00").unwrap();
+    assert!(cookie.extensions(&jpeg).unwrap().len() > 1);
+    assert_eq!(cookie.preferred_extension(&jpeg).unwrap(), Some("jpeg".to_string()));
+    assert_eq!(cookie.preferred_extension("data/db-python").unwrap(), None);
+    std::fs::remove_file(&jpeg).unwrap();
+}
+
+#[test]
+fn buffer_as_ref() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load(&["data/db-python"]).is_ok());
+
+    let s = "#!/usr/bin/env python\nprint('Hello, world!')";
+    assert_eq!(cookie.buffer(s).unwrap(), "text/x-python");
+    assert_eq!(cookie.buffer(s.as_bytes()).unwrap(), "text/x-python");
+    let string = String::from(s);
+    assert_eq!(cookie.buffer(&string).unwrap(), "text/x-python");
+    assert_eq!(cookie.buffer(string.into_bytes()).unwrap(), "text/x-python");
+}
+
+#[test]
+fn close() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    cookie.close();
+
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    cookie.close();
+}
+
+#[test]
+fn flags_as_map_keys() {
+    use std::collections::HashMap;
+
+    let mut cache = HashMap::new();
+    cache.insert((Flags::MIME_TYPE | Flags::COMPRESS, "data/db-images-png"), 1);
+    assert_eq!(cache.get(&(Flags::COMPRESS | Flags::MIME_TYPE, "data/db-images-png")), Some(&1));
+    assert_eq!(Flags::MIME_TYPE | Flags::MIME_ENCODING, Flags::MIME);
+    assert!(!cache.contains_key(&(Flags::MIME_TYPE, "data/db-images-png")));
+}
+
+#[cfg(unix)]
+#[test]
+fn collect_warnings() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+
+    let (loaded, warnings) = cookie.collect_warnings(|magic| magic.load(&["data/db-broken"])).unwrap();
+    assert!(loaded.is_err());
+    assert!(warnings.contains(&"data/db-broken, 3: Warning: type `bogustype x foo' invalid".to_string()));
+    assert!(!cookie.current_flags().contains(Flags::CHECK));
+
+    let (loaded, _) = cookie.collect_warnings(|magic| magic.load(&["data/db-images-png"])).unwrap();
+    assert!(loaded.is_ok());
+}
+
+#[test]
+fn get_buffer_structured_mime() {
+    let cookie = Magic::open(Flags::APPLE).unwrap();
+    assert!(cookie.load(&["data/db-python"]).is_ok());
+
+    let mime = cookie.buffer_mime("#!/usr/bin/env python\nprint('Hello, world!')").unwrap();
+    assert_eq!(mime.to_string(), "text/x-python; charset=us-ascii");
+    assert_eq!(cookie.current_flags(), Flags::APPLE | Flags::ERROR);
+}
+
+#[cfg(unix)]
+#[test]
+fn list_entries() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+
+    let entries = cookie.list_entries(&["data/db-images-png", "data/db-python"]).unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0], MagicEntry {
+        description: "PNG image data".to_string(),
+        mime_type: Some("image/png".to_string()),
+        strength: 110,
+        line: 1,
+        source_file: "data/db-images-png".into(),
+    });
+    assert!(entries[1..].iter().all(|e| e.source_file == std::path::Path::new("data/db-python")));
+    assert!(entries[1..].iter().all(|e| e.description == "Python script text executable"));
+    // Stronger entries are listed first
+    assert!(entries[1].strength >= entries[4].strength);
+}
+
+#[test]
+fn compression() {
+    let cookie = Magic::open(Flags::COMPRESS).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let gzip = cookie.compression("data/rust-logo-128x128-blk.png.gz").unwrap();
+    assert_eq!(gzip, Some("gzip".to_string()));
+    assert_eq!(cookie.compression("data/rust-logo-128x128-blk.png").unwrap(), None);
+    assert_eq!(cookie.current_flags(), Flags::COMPRESS | Flags::ERROR);
+}
+
+#[test]
+fn raw_flags() {
+    let unknown = 0x40000000;
+    let cookie = Magic::open_raw(Flags::MIME_TYPE.bits() | unknown).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert_eq!(cookie.current_flags().bits(), (Flags::MIME_TYPE | Flags::ERROR).bits() | unknown);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    assert!(cookie.set_flags_raw(Flags::NO_CHECK_JSON.bits()));
+    assert_eq!(cookie.current_flags(), Flags::NO_CHECK_JSON);
+    assert!(!cookie.buffer("{\"key\": 1}").unwrap().contains("JSON"));
+}
+
+#[test]
+fn ranked_matches() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let path = "data/rust-logo-128x128-blk.png";
+    let ranked = cookie.ranked_matches(path).unwrap();
+    let matches = cookie.file_all(path).unwrap();
+    assert_eq!(ranked.len(), matches.len());
+    assert_eq!(ranked[0], (0, "PNG image data, 128 x 128, 8-bit/color RGBA, non-interlaced".to_string()));
+    assert!(ranked.iter().enumerate().all(|(i, &(rank, ref m))| i == rank && *m == matches[i]));
+}
+
+#[cfg(unix)]
+#[test]
+fn descriptor_pipe() {
+    use std::{fs::File, io::{Read, Write}, os::unix::io::FromRawFd, thread};
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert!(cookie.set_param(Param::BytesMax, 1024));
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let (mut reader, mut writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    let mut data = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    data.resize(data.len() + 8192, 0);
+    let len = data.len();
+    let feeder = thread::spawn(move || writer.write_all(&data).unwrap());
+
+    assert_eq!(cookie.descriptor(fds[0]).unwrap(), "image/png");
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    feeder.join().unwrap();
+    // Only the prefix libmagic needed was consumed, the rest can still be read
+    assert_eq!(rest.len(), len - 1024);
+}
+
+#[test]
+fn common_mime() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert_eq!(cookie.common_mime("data/rust-logo-128x128-blk.png").unwrap(), CommonMime::Png);
+    assert_eq!(cookie.common_mime("data/rust-logo-128x128-blk.png.gz").unwrap(), CommonMime::Gzip);
+    assert_eq!(cookie.common_mime("data/latin1.txt").unwrap(), CommonMime::PlainText);
+    assert_eq!(cookie.common_mime("data").unwrap(), CommonMime::Other("inode/directory".to_string()));
+}
+
+#[test]
+fn flags_parse_list() {
+    assert_eq!(Flags::parse_list("mime_type, compress").unwrap(), Flags::MIME_TYPE | Flags::COMPRESS);
+    assert_eq!(Flags::parse_list("").unwrap(), Flags::NONE);
+    let flags = Flags::MIME | Flags::ERROR;
+    assert_eq!(Flags::parse_list(&flags.to_string()).unwrap(), flags);
+    assert_eq!(Flags::parse_list("mime,bogus").unwrap_err().desc(), "unknown flag `bogus`");
+}
+
+#[test]
+fn flags_unknown_bits_round_trip() {
+    use super::raw_flags;
+
+    let flags = raw_flags(0x40000200);
+    assert_eq!(flags.to_string(), "MAGIC_ERROR | 0x40000000");
+    assert_eq!(raw_flags(0x40000000).to_string(), "0x40000000");
+    assert_eq!(Flags::parse_list(&flags.to_string()).unwrap().bits(), 0x40000200);
+    assert_eq!("0x410".parse::<Flags>().unwrap(), Flags::MIME);
+    assert!("0xgarbage".parse::<Flags>().is_err());
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#"["MAGIC_ERROR","0x40000000"]"#);
+        assert_eq!(serde_json::from_str::<Flags>(&json).unwrap().bits(), 0x40000200);
+    }
+}
+
+#[test]
+fn open_from_env() {
+    use super::env_flags;
+
+    // The parsing is tested without touching the environment of the test process
+    assert_eq!(env_flags(Some("mime_type,compress")).unwrap(), Flags::MIME_TYPE | Flags::COMPRESS);
+    let err = env_flags(Some("mime_tpye")).unwrap_err();
+    assert_eq!(err.desc(), "MAGIC_FLAGS: unknown flag `mime_tpye`");
+    assert_eq!(env_flags(None).unwrap(), Flags::NONE);
+}
+
+#[cfg(unix)]
+#[test]
+fn load_with_status() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+
+    assert_eq!(cookie.load_with_status(&["data/db-images-png"]).unwrap(), LoadStatus::Full);
+
+    match cookie.load_with_status(&["data/db-broken", "data/db-images-png"]).unwrap() {
+        LoadStatus::Partial(warnings) => {
+            assert!(warnings.contains(&"data/db-broken, 3: Warning: type `bogustype x foo' invalid".to_string()))
+        }
+        status => panic!("unexpected {:?}", status),
+    }
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+
+    match cookie.load_with_status(&["data/db-broken"]).unwrap() {
+        LoadStatus::Failed(e) => assert!(e.desc().contains("Warning: type `bogustype x foo' invalid")),
+        status => panic!("unexpected {:?}", status),
+    }
+    assert!(!cookie.is_loaded());
+}
+
+#[cfg(unix)]
+#[test]
+fn nested_capture() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+
+    let (status, warnings) = cookie
+        .collect_warnings(|magic| magic.load_with_status(&["data/db-broken", "data/db-images-png"]))
+        .unwrap();
+    let warning = "data/db-broken, 3: Warning: type `bogustype x foo' invalid".to_string();
+    match status.unwrap() {
+        LoadStatus::Partial(inner) => assert!(inner.contains(&warning)),
+        status => panic!("unexpected {:?}", status),
+    }
+    // The outer capture sees what the inner one captured as well
+    assert!(warnings.contains(&warning));
+
+    let (listing, _) = cookie.collect_warnings(|magic| magic.list_to_string(&["data/db-images-png"])).unwrap();
+    assert!(listing.unwrap().contains("PNG image data"));
+}
+
+#[test]
+fn matches_mime() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let path = "data/rust-logo-128x128-blk.png";
+    assert!(cookie.matches_mime(path, &["image/png"]).unwrap());
+    assert!(cookie.matches_mime(path, &["text/plain", "image/*"]).unwrap());
+    assert!(cookie.matches_mime(path, &["*/*"]).unwrap());
+    assert!(cookie.matches_mime(path, &["Image/PNG"]).unwrap());
+    assert!(!cookie.matches_mime(path, &["image/jpeg", "text/*", "imag/*"]).unwrap());
+    assert!(!cookie.matches_mime(path, &[]).unwrap());
+    assert!(cookie.matches_mime("data/nonexistent", &["*/*"]).is_err());
+}
+
+#[test]
+fn precomputed_raw_flags() {
+    const BITS: libc::c_int = Flags::MIME_TYPE.bits() | Flags::COMPRESS.bits();
+
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    assert!(cookie.set_flags_raw(BITS));
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::COMPRESS);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png.gz").unwrap(), "image/png");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_target() {
+    let dir = std::env::temp_dir().join("filemagic-symlink-target");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let logo = std::fs::canonicalize("data/rust-logo-128x128-blk.png").unwrap();
+    std::os::unix::fs::symlink(&logo, dir.join("logo")).unwrap();
+    std::os::unix::fs::symlink("nonexistent target", dir.join("broken")).unwrap();
+
+    let cookie = Magic::open(Flags::SYMLINK | Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let target = cookie.symlink_target(dir.join("logo")).unwrap();
+    assert_eq!(target, Some(logo.to_str().unwrap().to_string()));
+    assert_eq!(cookie.symlink_target(dir.join("broken")).unwrap(), Some("nonexistent target".to_string()));
+    assert_eq!(cookie.symlink_target("data/rust-logo-128x128-blk.png").unwrap(), None);
+    assert_eq!(cookie.symlink_target("data/nonexistent").unwrap_err().errno(), libc::ENOENT);
+    assert_eq!(cookie.current_flags(), Flags::SYMLINK | Flags::MIME_TYPE | Flags::ERROR);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_magic() {
+    use std::{
+        future::Future,
+        sync::Arc,
+        task::{Context, Poll, Wake},
+        thread,
+    };
+    use super::AsyncMagic;
+
+    /// Wakes up the thread blocking on a future
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let cookie = AsyncMagic::new(cookie).unwrap();
+
+    let png = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    let pending = (0..4).map(|_| cookie.classify(png.clone())).collect::<Vec<_>>();
+    for classification in pending {
+        assert_eq!(block_on(classification).unwrap(), "image/png");
+    }
+    assert_eq!(block_on(cookie.classify(Vec::new())).unwrap(), "application/x-empty");
+}
+
+#[test]
+fn flag_names() {
+    assert_eq!(Flags::MIME_TYPE.name(), Some("MAGIC_MIME_TYPE"));
+    assert_eq!(Flags::MIME.name(), Some("MAGIC_MIME"));
+    assert_eq!(Flags::NONE.name(), Some("MAGIC_NONE"));
+    assert_eq!(Flags::NO_CHECK_ASCII.name(), Some("MAGIC_NO_CHECK_TEXT"));
+    assert_eq!((Flags::MIME_TYPE | Flags::COMPRESS).name(), None);
+}
+
+#[test]
+fn reset_param() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    let params = [
+        Param::IndirMax, Param::NameMax, Param::ElfPhnumMax, Param::ElfShnumMax,
+        Param::ElfNotesMax, Param::RegexMax, Param::BytesMax, Param::EncodingMax,
+    ];
+    for &param in &params {
+        assert_eq!(cookie.get_param(param), Some(param.default_value()));
+    }
+
+    assert!(cookie.set_param(Param::BytesMax, 1024));
+    assert!(cookie.reset_param(Param::BytesMax));
+    assert_eq!(cookie.get_param(Param::BytesMax), Some(7 * 1024 * 1024));
+}
+
+#[test]
+fn open_verified() {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    let cookie = Magic::open_verified(Flags::MIME_TYPE, &["data/db-images-png"], png, |r| r == "image/png").unwrap();
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+    assert!(Magic::open_verified::<&str, _>(Flags::NONE, &[], png, |r| r.starts_with("PNG")).is_ok());
+
+    let err = Magic::open_verified(Flags::NONE, &["data/nonexistent"], png, |_| true).err().unwrap();
+    assert!(err.desc().starts_with("data/nonexistent: "));
+
+    // A custom database is verified with a probe of its own
+    let python = b"#!/usr/bin/env python\nprint('Hello, world!')";
+    let is_python = |r: &str| r.starts_with("Python script");
+    assert!(Magic::open_verified(Flags::NONE, &["data/db-python"], python, is_python).is_ok());
+
+    // A database that knows nothing about the probe only answers with the generic fallback
+    let err = Magic::open_verified(Flags::NONE, &["data/db-python"], png, |r| r.starts_with("PNG")).err().unwrap();
+    assert_eq!(err.desc(), "self-test failed: data");
+}
+
+#[test]
+fn get_reader_structured_mime() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mut file = std::fs::File::open("data/rust-logo-128x128-blk.png").unwrap();
+    let mime = cookie.reader_mime(&mut file, 64).unwrap();
+    assert_eq!(mime.to_string(), "image/png; charset=binary");
+
+    // Only the prefix was consumed
+    let mut rest = Vec::new();
+    std::io::Read::read_to_end(&mut file, &mut rest).unwrap();
+    assert_eq!(rest.len() as u64 + 64, std::fs::metadata("data/rust-logo-128x128-blk.png").unwrap().len());
+}
+
+#[test]
+fn bounded_results() {
+    use super::{result_bytes, MAX_RESULT_LEN};
+
+    let terminated = b"PNG image data\0";
+    let bytes = unsafe { result_bytes(terminated.as_ptr() as *const libc::c_char) }.unwrap();
+    assert_eq!(bytes, b"PNG image data");
+
+    let unterminated = vec![b'a'; MAX_RESULT_LEN];
+    let err = unsafe { result_bytes(unterminated.as_ptr() as *const libc::c_char) }.unwrap_err();
+    assert_eq!(err.errno(), libc::EOVERFLOW);
+}
+
+#[test]
+fn tar_members() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let members = cookie.tar_members("data/members.tar").unwrap();
+    let long = format!("scripts/{}/hello.py", "x".repeat(120));
+    let expected = [
+        ("logo/rust-logo.png", "image/png"),
+        ("logo/", "directory"),
+        ("logo/empty", "application/x-empty"),
+        ("logo/latest.png", "symbolic link to rust-logo.png"),
+        (&long, "text/x-script.python"),
+    ];
+    assert_eq!(members.len(), expected.len());
+    for (member, &(name, description)) in members.iter().zip(&expected) {
+        assert_eq!(member.0, name);
+        assert_eq!(member.1, description);
+    }
+
+    assert!(cookie.tar_members("data/nonexistent").is_err());
+}
+
+#[test]
+fn tar_long_names() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    // Shorter than the GNU long name, which must still be read in full
+    assert!(cookie.set_param(Param::BytesMax, 64));
+
+    let members = cookie.tar_members("data/members.tar").unwrap();
+    let long = format!("scripts/{}/hello.py", "x".repeat(120));
+    assert_eq!(members.last().unwrap().0, long);
+
+    let members = cookie.tar_members("data/pax.tar").unwrap();
+    let long = format!("docs/{}/readme.txt", "y".repeat(120));
+    assert_eq!(members.len(), 2);
+    assert_eq!(members[0], (long.clone(), "text/plain".to_string()));
+    assert_eq!(members[1], ("docs/link".to_string(), format!("symbolic link to {}", long)));
+}
+
+#[test]
+fn encoding() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let dir = std::env::temp_dir().join("filemagic-encoding");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let utf16 = "\u{feff}Hello, world!\n"
+        .encode_utf16()
+        .flat_map(|c| c.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    std::fs::write(dir.join("utf16"), utf16).unwrap();
+    std::fs::write(dir.join("utf8"), "Grüße, world!\n").unwrap();
+    std::fs::write(dir.join("latin1"), b"Gr\xfc\xdfe, world!\n").unwrap();
+
+    assert_eq!(cookie.encoding(dir.join("utf16")).unwrap(), Encoding::Utf16Le);
+    assert_eq!(cookie.encoding(dir.join("utf8")).unwrap(), Encoding::Utf8);
+    assert_eq!(cookie.encoding(dir.join("latin1")).unwrap(), Encoding::Iso8859_1);
+    assert_eq!(cookie.encoding("data/latin1.txt").unwrap(), Encoding::UsAscii);
+    assert_eq!(cookie.encoding("data/rust-logo-128x128-blk.png").unwrap(), Encoding::Binary);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn same_type() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let png = "data/rust-logo-128x128-blk.png";
+    assert!(cookie.same_type(png, png).unwrap());
+    assert!(cookie.same_type(png, std::path::PathBuf::from(png)).unwrap());
+    assert!(!cookie.same_type(png, "data/latin1.txt").unwrap());
+    assert!(cookie.same_type(png, "data/nonexistent").is_err());
+}
+
+#[test]
+fn try_set_flags() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert!(cookie.try_set_flags(Flags::MIME_TYPE | Flags::PRESERVE_ATIME).is_ok());
+    assert_eq!(cookie.current_flags(), Flags::MIME_TYPE | Flags::PRESERVE_ATIME);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap(), "image/png");
+}
+
+#[test]
+fn is_recognized() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let data = std::env::temp_dir().join("filemagic-is-recognized");
+    std::fs::write(&data, [0x01, 0x02, 0x80, 0xfe, 0x00, 0x13, 0x37]).unwrap();
+    assert_eq!(cookie.file(&data).unwrap(), "data");
+    assert!(!cookie.is_recognized(&data).unwrap());
+    assert!(cookie.is_recognized("data/rust-logo-128x128-blk.png").unwrap());
+    std::fs::remove_file(&data).unwrap();
+}
+
+#[test]
+fn file_prefix() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let path = "data/rust-logo-128x128-blk.png";
+    assert_eq!(cookie.file_prefix(path, 8192).unwrap(), cookie.file(path).unwrap());
+    // Too short a prefix is no longer recognized as an image
+    assert!(!cookie.file_prefix(path, 4).unwrap().starts_with("PNG"));
+    assert_eq!(cookie.file_prefix("data/nonexistent", 8).unwrap_err().errno(), libc::ENOENT);
+}
+
+#[test]
+fn last_error_snapshot() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert_eq!(cookie.last_error(), None);
+    assert_eq!(cookie.file("data/rust-logo-128x128-blk.png").unwrap_err(), cookie.last_error().unwrap());
+
+    assert!(cookie.load_default().is_ok());
+    let err = cookie.file("data/nonexistent").unwrap_err();
+    assert_eq!(err.errno(), libc::ENOENT);
+
+    // Later successful calls keep the snapshot
+    assert!(cookie.file("data/rust-logo-128x128-blk.png").is_ok());
+    assert_eq!(cookie.last_error(), Some(err));
+
+    let err = cookie.load(&["data/nonexistent"]).unwrap_err();
+    assert_eq!(cookie.last_error(), Some(err));
+}
+
+#[test]
+fn is_archive() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    assert!(cookie.is_archive("data/members.tar").unwrap());
+    assert!(cookie.is_archive("data/rust-logo-128x128-blk.png.gz").unwrap());
+    assert!(!cookie.is_archive("data/rust-logo-128x128-blk.png").unwrap());
+    assert!(cookie.is_archive("data/nonexistent").is_err());
+}
+
+#[test]
+fn cursor() {
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let mut cursor = Cursor::new(Vec::new());
+    cursor.write_all(&std::fs::read("data/rust-logo-128x128-blk.png").unwrap()).unwrap();
+    assert_eq!(cookie.cursor(&cursor).unwrap(), "image/png");
+    cursor.seek(SeekFrom::Start(16)).unwrap();
+    assert_eq!(cookie.cursor(&cursor).unwrap(), cookie.buffer(cursor.get_ref()).unwrap());
+}
+
+#[test]
+fn classify_with_context() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let (flags, mime_type) = cookie.classify_with_context("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!((flags, mime_type.as_str()), (cookie.get_flags(), "image/png"));
+    assert!(flags.contains(Flags::MIME_TYPE));
+
+    let reproduced = Magic::open(flags).unwrap();
+    assert!(reproduced.load_default().is_ok());
+    assert_eq!(reproduced.file("data/rust-logo-128x128-blk.png").unwrap(), mime_type);
+
+    let cookie = Magic::open_raw(0x40000010).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let (flags, _) = cookie.classify_with_context("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(flags.bits() & 0x40000000, 0x40000000);
+}
+
+#[cfg(unix)]
+#[test]
+fn debug_trace() {
+    let cookie = Magic::open(Default::default()).unwrap();
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
+
+    let (description, trace) = cookie.debug_trace("data/rust-logo-128x128-blk.png").unwrap();
+    assert!(description.starts_with("PNG image data"));
+    assert!(!trace.is_empty());
+    assert!(!cookie.current_flags().contains(Flags::DEBUG));
+
+    assert!(cookie.debug_trace("data/none").is_err());
+}
+
+#[test]
+fn extension_candidates() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let jpeg = std::env::temp_dir().join("filemagic-extension-candidates");
+    std::fs::write(&jpeg, b"\xff\xd8\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00").unwrap();
+    assert_eq!(cookie.extension_candidates(&jpeg).unwrap(), vec!["jpeg", "jpg", "jpe", "jfif"]);
+    assert!(cookie.extension_candidates("data/db-python").unwrap().is_empty());
+    std::fs::remove_file(&jpeg).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn stdin() {
+    use std::{fs::File, os::unix::io::AsRawFd};
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    // Temporarily replace the standard input of the test process
+    let saved = unsafe { libc::dup(0) };
+    assert!(saved >= 0);
+    let png = File::open("data/rust-logo-128x128-blk.png").unwrap();
+    assert_eq!(unsafe { libc::dup2(png.as_raw_fd(), 0) }, 0);
+    assert_eq!(cookie.stdin().unwrap(), "image/png");
+    let empty = File::open("/dev/null").unwrap();
+    assert_eq!(unsafe { libc::dup2(empty.as_raw_fd(), 0) }, 0);
+    assert_eq!(cookie.stdin().unwrap(), "application/x-empty");
+    assert_eq!(unsafe { libc::dup2(saved, 0) }, 0);
+    unsafe { libc::close(saved) };
+}
+
+#[test]
+fn set_unknown_as() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let unknown = [0x00, 0xff, 0x13, 0x37, 0x80, 0x01];
+    assert_eq!(cookie.buffer(unknown).unwrap(), "data");
+
+    cookie.set_unknown_as(Some("unknown".to_string()));
+    assert_eq!(cookie.buffer(unknown).unwrap(), "unknown");
+    assert!(cookie.buffer("text\n").unwrap().contains("text"));
+    assert_eq!(cookie.buffer_mime(unknown).unwrap().to_string(), "application/octet-stream; charset=binary");
+    assert!(cookie.set_flags(Flags::MIME_TYPE | Flags::MIME_ENCODING));
+    assert_eq!(cookie.buffer(unknown).unwrap(), "unknown");
+
+    let clone = cookie.try_clone().unwrap();
+    assert_eq!(clone.buffer(unknown).unwrap(), "unknown");
+
+    cookie.set_unknown_as(None);
+    assert_eq!(cookie.buffer(unknown).unwrap(), "application/octet-stream; charset=binary");
+}
+
+#[test]
+fn classify_batch() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let paths = ["data/nonexistent", "data/rust-logo-128x128-blk.png", "data/none"];
+    let results = cookie.classify_batch(&paths);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].0, std::path::PathBuf::from("data/nonexistent"));
+    assert!(results[0].1.as_ref().unwrap_err().to_string().contains("data/nonexistent"));
+    assert_eq!(results[1].1.as_ref().unwrap(), "image/png");
+    assert!(results[2].1.as_ref().unwrap_err().to_string().contains("data/none"));
+}
+
+#[test]
+fn match_source() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load(&["data/db-images-png"]).is_ok());
+
+    assert_eq!(cookie.match_source("data/rust-logo-128x128-blk.png").unwrap(), MatchSource::Database);
+    assert_eq!(cookie.match_source("data/latin1.txt").unwrap(), MatchSource::Builtin);
+    assert!(!cookie.current_flags().contains(Flags::NO_CHECK_SOFT));
+    assert!(cookie.match_source("data/none").is_err());
+}
+
+#[test]
+fn unknown_as_verbatim() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    cookie.set_unknown_as(Some("unknown".to_string()));
+
+    assert_eq!(cookie.file("data/unknown.gz").unwrap(), "application/gzip");
+    assert_eq!(cookie.inner_type("data/unknown.gz").unwrap(), "application/octet-stream");
+    assert_eq!(cookie.container_type("data/unknown.gz").unwrap(), "application/gzip");
+    assert!(cookie.was_decompressed("data/unknown.gz").unwrap());
+    assert_eq!(cookie.match_source("data/unknown.gz").unwrap(), MatchSource::Database);
+    assert_eq!(cookie.buffer([0x00, 0xff, 0x13, 0x37]).unwrap(), "unknown");
+}
+
+#[test]
+fn is_empty_file() {
+    let cookie = Magic::open(Flags::NONE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let empty = std::env::temp_dir().join("filemagic-is-empty-file");
+    std::fs::write(&empty, b"").unwrap();
+    assert!(cookie.is_empty_file(&empty).unwrap());
+    assert!(!cookie.is_empty_file("data/rust-logo-128x128-blk.png").unwrap());
+    assert!(cookie.is_empty_file("data/none").is_err());
+    std::fs::remove_file(&empty).unwrap();
+    #[cfg(unix)]
+    assert!(cookie.is_empty_file("/dev/null").unwrap());
+
+    assert!(cookie.is_empty_buffer(b""));
+    assert!(!cookie.is_empty_buffer([0]));
+}
+
+#[test]
+fn executable_arch() {
+    use super::executable_arch;
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let test_binary = std::env::current_exe().unwrap();
+    assert!(cookie.executable_arch(&test_binary).unwrap().is_some());
+    assert_eq!(cookie.executable_arch("data/rust-logo-128x128-blk.png").unwrap(), None);
+    assert!(cookie.executable_arch("data/none").is_err());
+
+    let elf = "ELF 64-bit LSB pie executable, x86-64, version 1 (SYSV), dynamically linked";
+    assert_eq!(executable_arch(elf).as_deref(), Some("x86-64"));
+    let elf = "ELF 64-bit LSB shared object, ARM aarch64, version 1 (SYSV), dynamically linked";
+    assert_eq!(executable_arch(elf).as_deref(), Some("ARM aarch64"));
+    let pe = "PE32 executable (console) Intel 80386, for MS Windows, 5 sections";
+    assert_eq!(executable_arch(pe).as_deref(), Some("Intel 80386"));
+    let pe = "PE32+ executable (DLL) (GUI) x86-64, for MS Windows";
+    assert_eq!(executable_arch(pe).as_deref(), Some("x86-64"));
+    let pe = "PE32+ executable for EFI (application), x86-64, 5 sections";
+    assert_eq!(executable_arch(pe).as_deref(), Some("x86-64"));
+    let pe = "PE32 executable (console) Intel 80386 Mono/.Net assembly, for MS Windows";
+    assert_eq!(executable_arch(pe).as_deref(), Some("Intel 80386"));
+    let mach_o = "Mach-O 64-bit arm64 executable, flags:<NOUNDEFS|DYLDLINK|TWOLEVEL|PIE>";
+    assert_eq!(executable_arch(mach_o).as_deref(), Some("arm64"));
+    assert_eq!(executable_arch("Mach-O 64-bit x86_64 executable").as_deref(), Some("x86_64"));
+    assert_eq!(executable_arch("Mach-O i386 executable").as_deref(), Some("i386"));
+    assert_eq!(executable_arch("Mach-O universal binary with 2 architectures"), None);
+    assert_eq!(executable_arch("PNG image data, 128 x 128"), None);
+}
+
+#[test]
+fn classifying_reader() {
+    use std::io::Read;
+
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+
+    let data = std::fs::read("data/rust-logo-128x128-blk.png").unwrap();
+    let mut reader = cookie.classifying_reader(&data[..], 1024);
+    assert!(reader.classified_as().is_none());
+    let mut first = [0; 16];
+    reader.read_exact(&mut first).unwrap();
+    assert_eq!(reader.classified_as().unwrap().as_ref().unwrap(), "image/png");
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!([&first[..], &rest[..]].concat(), data);
+
+    let mut reader = cookie.classifying_reader(&b""[..], 1024);
+    assert_eq!(reader.read(&mut first).unwrap(), 0);
+    assert_eq!(reader.classified_as().unwrap().as_ref().unwrap(), "application/x-empty");
+}
+
+#[test]
+fn tar_gnu_header() {
+    use super::tar::next_member;
+
+    let mut header = vec![0u8; 512];
+    header[..9].copy_from_slice(b"hello.txt");
+    header[124..136].copy_from_slice(b"00000000000\0");
+    header[156] = b'0';
+    header[257..265].copy_from_slice(b"ustar  \0");
+    // The access time of an incremental GNU archive, where ustar has the name prefix
+    header[345..357].copy_from_slice(b"14417120167\0");
+    let member = next_member(&mut std::io::Cursor::new(&header), 1024).unwrap().unwrap();
+    assert_eq!(member.name, "hello.txt");
+
+    header[257..265].copy_from_slice(b"ustar\x0000");
+    header[345..357].copy_from_slice(b"some/prefix\0");
+    let member = next_member(&mut std::io::Cursor::new(&header), 1024).unwrap().unwrap();
+    assert_eq!(member.name, "some/prefix/hello.txt");
+}
+
+#[test]
+fn output_flags_single_result() {
+    let cookie = Magic::open(Flags::CONTINUE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let png = "data/rust-logo-128x128-blk.png";
+
+    let mime = cookie.mime(png).unwrap();
+    assert_eq!((mime.type_.as_str(), mime.subtype.as_str()), ("image", "png"));
+    assert_eq!(cookie.extensions(png).unwrap(), vec!["png"]);
+    assert_eq!(cookie.common_mime(png).unwrap(), CommonMime::Png);
+    assert!(cookie.is_recognized(png).unwrap());
+    assert!(cookie.current_flags().contains(Flags::CONTINUE));
+}
+
+#[test]
+fn with_flags_restores_on_error() {
+    let cookie = Magic::open(Flags::MIME_TYPE).unwrap();
+    assert!(cookie.load_default().is_ok());
+    let flags = cookie.current_flags();
+
+    assert!(cookie.inner_type("data/none").is_err());
+    assert!(cookie.extensions("data/none").is_err());
+    assert_eq!(cookie.current_flags(), flags);
+    assert_eq!(cookie.get_flags() - Flags::CHECK, flags);
+}
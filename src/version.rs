@@ -1,3 +1,7 @@
+use libc::c_int;
+
+use api;
+
 /// Returns the version of this crate in the format `MAJOR.MINOR.PATCH`.
 pub fn version() -> &'static str {
     // TODO: There's also an optional _PRE part
@@ -9,3 +13,8 @@ pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION_PATCH"),
     )
 }
+
+/// Returns the version of the linked `libmagic`, e.g. `544` for `5.44`.
+pub fn libmagic_version() -> c_int {
+    unsafe { api::magic_version() }
+}